@@ -0,0 +1,74 @@
+//! Compares looking entities up through `Data`'s shared, indexed storage
+//! against the deep-copying linear scans it replaced.
+//!
+//! Run with `cargo bench --bench lookups`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lucos_configy_api::data::{Data, Host, System};
+use std::fmt::Write as _;
+use std::fs;
+use tempfile::tempdir;
+
+/// Generates a config with the given number of hosts, and ten systems per host.
+fn generate_data(host_count: usize) -> Data {
+	let dir = tempdir().unwrap();
+	let mut hosts = String::new();
+	let mut systems = String::new();
+	for h in 0..host_count {
+		writeln!(hosts, "host{h:05}:\n  domain: h{h}.example.com\n  ipv4: 10.0.{}.{}\n  serves_http: true", h / 256, h % 256).unwrap();
+		for s in 0..10 {
+			writeln!(systems, "system{h:05}_{s}:\n  domain: s{s}.h{h}.example.com\n  http_port: {}\n  hosts: [host{h:05}]", 8000 + s).unwrap();
+		}
+	}
+	fs::write(dir.path().join("hosts.yaml"), hosts).unwrap();
+	fs::write(dir.path().join("systems.yaml"), systems).unwrap();
+	for kind in ["volumes", "components", "scripts"] {
+		fs::write(dir.path().join(format!("{kind}.yaml")), "{}").unwrap();
+	}
+	Data::from_dir(dir.path()).unwrap()
+}
+
+/// How hosts used to be looked up: clone every host, then scan for a match.
+/// Neither side serialises the host it finds, as both would do so equally.
+fn get_host_by_cloning(data: &Data, id: &str) -> Option<Host> {
+	let hosts: Vec<Host> = data.get_hosts().iter().map(|host| Host::clone(host)).collect();
+	hosts.into_iter()
+		.find(|host| host.id.as_deref() == Some(id))
+}
+
+/// How filtered lists used to be built: clone every system, then filter the copies.
+fn get_http_systems_by_cloning(data: &Data) -> Vec<System> {
+	let systems: Vec<System> = data.get_systems().iter().map(|system| System::clone(system)).collect();
+	systems.into_iter()
+		.filter(|system| system.http_port.is_some())
+		.collect()
+}
+
+fn lookups(c: &mut Criterion) {
+	let mut group = c.benchmark_group("get_host");
+	for host_count in [10, 100, 1000] {
+		let data = generate_data(host_count);
+		let id = format!("host{:05}", host_count - 1);
+		group.bench_with_input(BenchmarkId::new("indexed", host_count), &id, |b, id| {
+			b.iter(|| black_box(data.get_host(id)).is_some())
+		});
+		group.bench_with_input(BenchmarkId::new("cloned", host_count), &id, |b, id| {
+			b.iter(|| black_box(get_host_by_cloning(&data, id)).is_some())
+		});
+	}
+	group.finish();
+
+	let mut group = c.benchmark_group("http_systems");
+	for host_count in [10, 100, 1000] {
+		let data = generate_data(host_count);
+		group.bench_function(BenchmarkId::new("borrowed", host_count), |b| {
+			b.iter(|| black_box(data.get_systems_filtered(|system| system.http_port.is_some())).len())
+		});
+		group.bench_function(BenchmarkId::new("cloned", host_count), |b| {
+			b.iter(|| black_box(get_http_systems_by_cloning(&data)).len())
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+use axum::{
+	extract::State,
+	response::{IntoResponse, Response},
+	http::{header, StatusCode},
+	http::header::HeaderMap,
+	Json,
+};
+use sha2::{Digest, Sha256};
+use crate::store::AppState;
+
+/// Whether the request's `Authorization: Bearer` token matches the configured admin token.
+fn is_authorised(headers: &HeaderMap, admin_token: &str) -> bool {
+	let Some(given) = headers.get(header::AUTHORIZATION)
+		.and_then(|h| h.to_str().ok())
+		.and_then(|h| h.strip_prefix("Bearer "))
+	else {
+		return false;
+	};
+	// Compare hashes of both, byte by byte, so the time taken reveals neither how much of the token was right nor its length
+	let (given, expected) = (Sha256::digest(given), Sha256::digest(admin_token));
+	given.iter().zip(expected.iter()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Reload the config from disk, as if it had just changed.
+/// Responds with the outcome, with a 422 status if the reloaded config had errors.
+pub async fn reload(
+	State(state): State<AppState>,
+	headers: HeaderMap,
+) -> Response {
+	let Some(admin_token) = &state.admin_token else {
+		return (StatusCode::FORBIDDEN, "Admin endpoints are disabled, as no ADMIN_TOKEN is set").into_response();
+	};
+	if !is_authorised(&headers, admin_token) {
+		return (
+			StatusCode::UNAUTHORIZED,
+			[(header::WWW_AUTHENTICATE, "Bearer")],
+			"A valid admin token is required",
+		).into_response();
+	}
+	// Reloading reads and parses every config file, so keep it off the async runtime's threads
+	let outcome = tokio::task::spawn_blocking({
+		let store = state.store.clone();
+		move || store.reload_outcome()
+	}).await.expect("reloading config panicked");
+	println!("{} (requested via /_admin/reload)", outcome);
+	let status = if outcome.ok { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+	(status, Json(outcome)).into_response()
+}
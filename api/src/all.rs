@@ -1,20 +1,26 @@
 use std::sync::Arc;
 use axum::{
-	extract::State,
+	Extension,
+	extract::Query,
 	response::{IntoResponse, Response},
 	http::{header, StatusCode},
 	http::header::HeaderMap,
 };
 use std::str::FromStr;
 use mime::Mime;
-use crate::conneg::negotiate;
+use crate::conneg::{negotiate, fill_defaults, omit_nulls, Defaults, Nulls, Params};
 use crate::data::{Data, System, Host, Volume, Component, Script};
 
+/// Serialises an entity as turtle, for the `/all` endpoint.
+pub trait ToTurtle {
+	fn to_turtle(&self, base: &str) -> String;
+}
+
 fn escape_turtle_literal(s: &str) -> String {
 	s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\r', "\\r")
 }
 
-fn turtle_ontology() -> String {
+fn turtle_ontology(data: &Data) -> String {
 	let mut out = String::new();
 
 	out.push_str("# Ontology\n");
@@ -23,14 +29,8 @@ fn turtle_ontology() -> String {
 	// (arachne ingestor requires type metadata inline — see lucos_arachne#371)
 	out.push_str("\neolas:Technological\n    skos:prefLabel \"Technological\" .\n");
 
-	for (class, label) in &[
-		("System", "System"),
-		("Host", "Host"),
-		("Volume", "Volume"),
-		("Component", "Component"),
-		("Script", "Script"),
-		("PublicPort", "Public Port"),
-	] {
+	let classes = data.collections().map(|collection| (collection.turtle_class(), collection.turtle_class()));
+	for (class, label) in classes.iter().chain(&[("PublicPort", "Public Port")]) {
 		out.push_str(&format!(
 			"\nconfigy:{class}\n    a owl:Class ;\n    skos:prefLabel \"{label}\" ;\n    eolas:hasCategory eolas:Technological .\n"
 		));
@@ -46,6 +46,7 @@ fn turtle_ontology() -> String {
 		("ipv4", "IPv4 Address", "configy:Host", "xsd:string"),
 		("ipv6", "IPv6 Address", "configy:Host", "xsd:string"),
 		("ipv4Nat", "IPv4 NAT Address", "configy:Host", "xsd:string"),
+		("lanOnly", "LAN Only", "configy:Host", "xsd:boolean"),
 		("servesHttp", "Serves HTTP", "configy:Host", "xsd:boolean"),
 		("sshGateway", "SSH Gateway", "configy:Host", "configy:Host"),
 		("backupRoot", "Backup Root", "configy:Host", "xsd:string"),
@@ -53,6 +54,7 @@ fn turtle_ontology() -> String {
 		("shellFlavour", "Shell Flavour", "configy:Host", "xsd:string"),
 		("canReachExternalServices", "Can Reach External Services", "configy:Host", "xsd:boolean"),
 		("firewallEnforce", "Firewall Enforce", "configy:Host", "xsd:boolean"),
+		("status", "Status", "configy:Host", "xsd:string"),
 		("recreateEffort", "Recreate Effort", "configy:Volume", "xsd:string"),
 		("backupStrategy", "Backup Strategy", "configy:Volume", "xsd:string"),
 		("skipBackup", "Skip Backup", "configy:Volume", "xsd:boolean"),
@@ -70,12 +72,13 @@ fn turtle_ontology() -> String {
 	out
 }
 
-fn turtle_systems(systems: &[System], base: &str) -> String {
-	let mut out = String::new();
-	for system in systems {
+impl ToTurtle for System {
+	fn to_turtle(&self, base: &str) -> String {
+		let system = self;
+		let mut out = String::new();
 		let id = match &system.id {
 			Some(id) => id,
-			None => continue,
+			None => return out,
 		};
 		out.push_str(&format!("\n<{base}/systems#{id}>\n    a configy:System ;\n    skos:prefLabel \"{}\"", escape_turtle_literal(id)));
 		if let Some(domain) = &system.domain {
@@ -103,29 +106,33 @@ fn turtle_systems(systems: &[System], base: &str) -> String {
 			));
 		}
 		out.push_str(" .\n");
+		out
 	}
-	out
 }
 
-fn turtle_hosts(hosts: &[Host], base: &str) -> String {
-	let mut out = String::new();
-	for host in hosts {
+impl ToTurtle for Host {
+	fn to_turtle(&self, base: &str) -> String {
+		let host = self;
+		let mut out = String::new();
 		let id = match &host.id {
 			Some(id) => id,
-			None => continue,
+			None => return out,
 		};
 		out.push_str(&format!("\n<{base}/hosts#{id}>\n    a configy:Host ;\n    skos:prefLabel \"{}\"", escape_turtle_literal(id)));
 		if let Some(domain) = &host.domain {
 			out.push_str(&format!(" ;\n    configy:domain \"{}\"", escape_turtle_literal(domain)));
 		}
 		if let Some(ipv4) = &host.ipv4 {
-			out.push_str(&format!(" ;\n    configy:ipv4 \"{}\"", escape_turtle_literal(ipv4)));
+			out.push_str(&format!(" ;\n    configy:ipv4 \"{ipv4}\""));
 		}
 		if let Some(ipv6) = &host.ipv6 {
-			out.push_str(&format!(" ;\n    configy:ipv6 \"{}\"", escape_turtle_literal(ipv6)));
+			out.push_str(&format!(" ;\n    configy:ipv6 \"{ipv6}\""));
 		}
 		if let Some(nat) = &host.ipv4_nat {
-			out.push_str(&format!(" ;\n    configy:ipv4Nat \"{}\"", escape_turtle_literal(nat)));
+			out.push_str(&format!(" ;\n    configy:ipv4Nat \"{nat}\""));
+		}
+		if host.lan_only {
+			out.push_str(" ;\n    configy:lanOnly true");
 		}
 		if host.serves_http {
 			out.push_str(" ;\n    configy:servesHttp true");
@@ -140,7 +147,7 @@ fn turtle_hosts(hosts: &[Host], base: &str) -> String {
 			out.push_str(" ;\n    configy:isStorageOnly true");
 		}
 		if let Some(shell_flavour) = &host.shell_flavour {
-			out.push_str(&format!(" ;\n    configy:shellFlavour \"{}\"", escape_turtle_literal(shell_flavour)));
+			out.push_str(&format!(" ;\n    configy:shellFlavour \"{}\"", shell_flavour.as_str()));
 		}
 		if !host.can_reach_external_services {
 			out.push_str(" ;\n    configy:canReachExternalServices false");
@@ -148,26 +155,30 @@ fn turtle_hosts(hosts: &[Host], base: &str) -> String {
 		if host.firewall_enforce {
 			out.push_str(" ;\n    configy:firewallEnforce true");
 		}
+		if host.status != crate::data::HostStatus::Active {
+			out.push_str(&format!(" ;\n    configy:status \"{}\"", host.status.as_str()));
+		}
 		out.push_str(" .\n");
+		out
 	}
-	out
 }
 
-fn turtle_volumes(volumes: &[Volume], base: &str) -> String {
-	let mut out = String::new();
-	for volume in volumes {
+impl ToTurtle for Volume {
+	fn to_turtle(&self, base: &str) -> String {
+		let volume = self;
+		let mut out = String::new();
 		let id = match &volume.id {
 			Some(id) => id,
-			None => continue,
+			None => return out,
 		};
 		out.push_str(&format!("\n<{base}/volumes#{id}>\n    a configy:Volume ;\n    skos:prefLabel \"{}\"", escape_turtle_literal(id)));
 		if let Some(desc) = &volume.description {
 			out.push_str(&format!(" ;\n    dc:description \"{}\"", escape_turtle_literal(desc)));
 		}
 		if let Some(effort) = &volume.recreate_effort {
-			out.push_str(&format!(" ;\n    configy:recreateEffort \"{}\"", escape_turtle_literal(effort)));
+			out.push_str(&format!(" ;\n    configy:recreateEffort \"{}\"", effort.as_str()));
 		}
-		out.push_str(&format!(" ;\n    configy:backupStrategy \"{}\"", escape_turtle_literal(&volume.backup_strategy)));
+		out.push_str(&format!(" ;\n    configy:backupStrategy \"{}\"", volume.backup_strategy.as_str()));
 		if volume.skip_backup {
 			out.push_str(" ;\n    configy:skipBackup true");
 		}
@@ -175,40 +186,42 @@ fn turtle_volumes(volumes: &[Volume], base: &str) -> String {
 			out.push_str(&format!(" ;\n    configy:skipBackupOnHost <{base}/hosts#{host}>"));
 		}
 		out.push_str(" .\n");
+		out
 	}
-	out
 }
 
-fn turtle_components(components: &[Component], base: &str) -> String {
-	let mut out = String::new();
-	for component in components {
+impl ToTurtle for Component {
+	fn to_turtle(&self, base: &str) -> String {
+		let component = self;
+		let mut out = String::new();
 		let id = match &component.id {
 			Some(id) => id,
-			None => continue,
+			None => return out,
 		};
 		out.push_str(&format!("\n<{base}/components#{id}>\n    a configy:Component ;\n    skos:prefLabel \"{}\"", escape_turtle_literal(id)));
 		if component.unsupervised_agent_code {
 			out.push_str(" ;\n    configy:unsupervisedAgentCode true");
 		}
 		out.push_str(" .\n");
+		out
 	}
-	out
 }
 
-fn turtle_scripts(scripts: &[Script], base: &str) -> String {
-	let mut out = String::new();
-	for script in scripts {
+impl ToTurtle for Script {
+	fn to_turtle(&self, base: &str) -> String {
+		let script = self;
+		let mut out = String::new();
 		let id = match &script.id {
 			Some(id) => id,
-			None => continue,
+			None => return out,
 		};
 		out.push_str(&format!("\n<{base}/scripts#{id}>\n    a configy:Script ;\n    skos:prefLabel \"{}\"", escape_turtle_literal(id)));
 		if script.unsupervised_agent_code {
 			out.push_str(" ;\n    configy:unsupervisedAgentCode true");
 		}
 		out.push_str(" .\n");
+		out
 	}
-	out
 }
 
 pub fn to_turtle(data: &Data, base: &str) -> String {
@@ -221,37 +234,33 @@ pub fn to_turtle(data: &Data, base: &str) -> String {
 	out.push_str("@prefix dc: <http://purl.org/dc/elements/1.1/> .\n");
 	out.push_str("@prefix eolas: <https://eolas.l42.eu/ontology/> .\n");
 	out.push_str(&format!("@prefix configy: <{base}/ontology#> .\n"));
+	out.push_str(&format!("# Config version: {}\n", data.version()));
 
-	out.push_str("\n");
-	out.push_str(&turtle_ontology());
+	out.push('\n');
+	out.push_str(&turtle_ontology(data));
 
-	out.push_str("\n# Systems\n");
-	out.push_str(&turtle_systems(&data.get_systems(), base));
-
-	out.push_str("\n# Hosts\n");
-	out.push_str(&turtle_hosts(&data.get_hosts(), base));
-
-	out.push_str("\n# Volumes\n");
-	out.push_str(&turtle_volumes(&data.get_volumes(), base));
-
-	out.push_str("\n# Components\n");
-	out.push_str(&turtle_components(&data.get_components(), base));
-
-	out.push_str("\n# Scripts\n");
-	out.push_str(&turtle_scripts(&data.get_scripts(), base));
+	for collection in data.collections() {
+		let kind = collection.kind();
+		out.push_str(&format!("\n# {}{}\n", kind[..1].to_uppercase(), &kind[1..]));
+		out.push_str(&collection.to_turtle(base));
+	}
 
 	out
 }
 
 pub async fn all(
-	State(data): State<Arc<Data>>,
+	Extension(data): Extension<Arc<Data>>,
 	headers: HeaderMap,
+	Query(params): Query<Params>,
 ) -> Response {
 	let available_mimes = vec![
 		Mime::from_str("text/turtle").unwrap(),
 		mime::APPLICATION_JSON,
 	];
-	let mime = negotiate(&headers, available_mimes);
+	let mime = match negotiate(&headers, available_mimes) {
+		Ok(mime) => mime,
+		Err(not_acceptable) => return not_acceptable.into_response(),
+	};
 
 	if mime.essence_str() == "text/turtle" {
 		let base = std::env::var("APP_ORIGIN").unwrap_or_else(|_| "https://configy.l42.eu".to_string());
@@ -263,13 +272,22 @@ pub async fn all(
 			.unwrap();
 	}
 
-	// Fallback: combined JSON
-	let combined = serde_json::json!({
-		"systems": data.get_systems(),
-		"hosts": data.get_hosts(),
-		"volumes": data.get_volumes(),
-		"components": data.get_components(),
-		"scripts": data.get_scripts(),
-	});
+	// Fallback: combined JSON, which (unlike turtle) has room for the `nulls` and `defaults` params
+	let mut combined: serde_json::Map<String, serde_json::Value> = data.collections().iter()
+		.map(|collection| {
+			let mut records = collection.to_value();
+			if let serde_json::Value::Array(items) = &mut records {
+				if params.defaults == Defaults::Explicit {
+					let defaults = collection.defaults();
+					items.iter_mut().for_each(|record| fill_defaults(record, &defaults));
+				}
+				if params.nulls == Nulls::Omit {
+					items.iter_mut().for_each(omit_nulls);
+				}
+			}
+			(collection.kind().to_string(), records)
+		})
+		.collect();
+	combined.insert("version".to_string(), data.version().into());
 	axum::Json(combined).into_response()
 }
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+use axum::{
+	extract::{Request, State},
+	http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use httpdate::HttpDate;
+use sha2::{Digest, Sha256};
+use crate::data::Data;
+use crate::routing::CONFIG_VERSION_HEADER;
+use crate::store::ConfigStore;
+
+/// A strong ETag for a single representation of a resource.
+///
+/// Responses from the data endpoints depend only on the config, the uri (which includes any `fields`)
+/// and the format they were negotiated into, so hashing those together identifies the response.
+fn etag(data: &Data, uri: &str, content_type: Option<&HeaderValue>) -> HeaderValue {
+	let mut hasher = Sha256::new();
+	for part in [data.version().as_bytes(), uri.as_bytes(), content_type.map(HeaderValue::as_bytes).unwrap_or_default()] {
+		hasher.update(part);
+		hasher.update([0]);
+	}
+	let hash: String = hasher.finalize().iter().take(16).map(|byte| format!("{:02x}", byte)).collect();
+	HeaderValue::from_str(&format!("\"{}\"", hash)).expect("quoted hex is always a valid header value")
+}
+
+/// Whether an `If-None-Match` header matches the given ETag, using weak comparison as RFC 9110 requires.
+fn matches_etag(if_none_match: &str, etag: &HeaderValue) -> bool {
+	let etag = etag.to_str().unwrap_or_default();
+	if_none_match.split(',')
+		.map(|candidate| candidate.trim())
+		.any(|candidate| candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag)
+}
+
+/// Whether the client's copy, as described by its conditional headers, is still current.
+fn is_not_modified(headers: &HeaderMap, etag: &HeaderValue, last_modified: SystemTime) -> bool {
+	// If-Modified-Since is ignored whenever If-None-Match is given
+	if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+		return if_none_match.to_str().is_ok_and(|value| matches_etag(value, etag));
+	}
+	// HttpDates only have a precision of seconds, so converting to them truncates `last_modified` to match the header
+	headers.get(header::IF_MODIFIED_SINCE)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<HttpDate>().ok())
+		.is_some_and(|since| HttpDate::from(last_modified) <= since)
+}
+
+/// Takes the snapshot of the config which the data endpoints serve, passing it to them as a request extension,
+/// so their response and its validators are always generated from the same version.
+///
+/// Adds `ETag` and `Last-Modified` headers to successful responses, and turns them into `304 Not Modified`
+/// when the client's copy is current.  Anything else (eg a 404, or a 406 from content negotiation) is passed on as it is.
+pub async fn conditional_get(State(store): State<Arc<ConfigStore>>, mut request: Request, next: Next) -> Response {
+	let data = store.current();
+	request.extensions_mut().insert(data.clone());
+	let is_get = request.method() == Method::GET || request.method() == Method::HEAD;
+	let uri = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or_default().to_string();
+	let conditions = request.headers().clone();
+
+	let mut response = next.run(request).await;
+	let version = HeaderValue::from_str(data.version()).expect("versions are hex, so always valid header values");
+	response.headers_mut().insert(CONFIG_VERSION_HEADER, version);
+	if !is_get || response.status() != StatusCode::OK {
+		return response;
+	}
+	let etag = etag(&data, &uri, response.headers().get(header::CONTENT_TYPE));
+	let last_modified = HeaderValue::from_str(&HttpDate::from(data.loaded_at()).to_string()).expect("http dates are valid header values");
+	let not_modified = is_not_modified(&conditions, &etag, data.loaded_at());
+	let headers = response.headers_mut();
+	headers.insert(header::ETAG, etag);
+	headers.insert(header::LAST_MODIFIED, last_modified);
+	headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+	if !not_modified {
+		return response;
+	}
+	// Only the headers which describe the unchanged response are kept, now there's no body for the rest to apply to
+	let mut not_modified = StatusCode::NOT_MODIFIED.into_response();
+	for name in [header::ETAG.as_str(), header::LAST_MODIFIED.as_str(), header::VARY.as_str(), CONFIG_VERSION_HEADER] {
+		if let Some(value) = response.headers().get(name) {
+			not_modified.headers_mut().insert(name, value.clone());
+		}
+	}
+	not_modified
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn if_none_match_uses_weak_comparison() {
+		let etag = HeaderValue::from_static("\"abc\"");
+		assert!(matches_etag("\"abc\"", &etag));
+		assert!(matches_etag("W/\"abc\"", &etag));
+		assert!(matches_etag("\"xyz\", \"abc\"", &etag));
+		assert!(matches_etag("*", &etag));
+		assert!(!matches_etag("\"xyz\"", &etag));
+		assert!(!matches_etag("abc", &etag));
+	}
+}
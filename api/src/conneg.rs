@@ -8,108 +8,457 @@ use axum::{
 };
 use axum_yaml::Yaml;
 use std::str::FromStr;
-use std::collections::HashSet;
 use mime::Mime;
 use serde_json::{Value, Map};
 use serde::Deserialize;
+use crate::fields::{FieldSelection, UnknownFields};
+use crate::registry::Record;
+
+/// A single media range from an `Accept` header, eg `text/csv;header=absent;q=0.5`.
+#[derive(Debug, PartialEq)]
+struct MediaRange {
+	type_: String,
+	subtype: String,
+	/// Any parameters before the weight, which are passed on to the chosen type (eg `header=absent` for CSV).
+	params: Vec<(String, String)>,
+	/// The weight, in thousandths, so that it can be compared exactly.
+	quality: u16,
+}
 
-pub fn negotiate(headers: &HeaderMap, available_mimes: Vec<Mime>) -> Mime {
-	let accept_string = headers.get(http::header::ACCEPT)
-		.and_then(|h| h.to_str().ok())
-		.unwrap_or("");
+impl MediaRange {
+	/// How specifically the range matches the given type, for choosing between several which match it.
+	/// `*/*` is the least specific, then `type/*`, then `type/subtype` with parameters the type doesn't have
+	/// (which asks for a variant of it, as with `text/csv;header=absent`), then `type/subtype`,
+	/// then `type/subtype` with parameters which the type has too.
+	fn specificity(&self, mime: &Mime) -> u8 {
+		match (self.type_.as_str(), self.subtype.as_str()) {
+			("*", _) => 0,
+			(_, "*") => 1,
+			_ if self.params.is_empty() => 3,
+			_ if self.params.iter().all(|(name, value)| mime.get_param(name.as_str()).is_some_and(|param| param.as_str() == value)) => 4,
+			_ => 2,
+		}
+	}
 
-	let mut mime_types: Vec<Mime> = accept_string.split(",") // HACK: Technically splitting on comma isn't fully standards-compliant, but it covers the vast majority of cases.
-		.filter_map(|mime_str| Mime::from_str(mime_str.trim()).ok())
-		.collect();
+	fn matches(&self, mime: &Mime) -> bool {
+		(self.type_ == "*" || self.type_.eq_ignore_ascii_case(mime.type_().as_str()))
+			&& (self.subtype == "*" || self.subtype.eq_ignore_ascii_case(mime.subtype().as_str()))
+	}
+}
+
+/// Split a header value on the given delimiter, ignoring any within quoted strings.
+fn split_unquoted(value: &str, delimiter: char) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let mut start = 0;
+	let mut in_quotes = false;
+	let mut escaped = false;
+	for (index, character) in value.char_indices() {
+		match character {
+			_ if escaped => escaped = false,
+			'\\' if in_quotes => escaped = true,
+			'"' => in_quotes = !in_quotes,
+			_ if character == delimiter && !in_quotes => {
+				parts.push(&value[start..index]);
+				start = index + 1;
+			},
+			_ => {},
+		}
+	}
+	parts.push(&value[start..]);
+	parts
+}
+
+fn is_token(value: &str) -> bool {
+	!value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+}
+
+/// Parse a weight as defined by RFC 9110: between 0 and 1, with at most three decimal places.
+fn parse_quality(value: &str) -> Option<u16> {
+	let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+	if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+	let thousandths = format!("{:0<3}", fraction).parse::<u16>().ok()?;
+	match whole {
+		"0" => Some(thousandths),
+		"1" if thousandths == 0 => Some(1000),
+		_ => None,
+	}
+}
 
-	mime_types.sort_by(|a, b| {
-		let weight_a: f32 = a.get_param("q").and_then(|n| n.as_str().trim().parse::<f32>().ok()).unwrap_or(1.0);
-		let weight_b: f32 = b.get_param("q").and_then(|n| n.as_str().trim().parse::<f32>().ok()).unwrap_or(1.0);
-		weight_b.partial_cmp(&weight_a).unwrap()
-	});
-
-	for target_mime in mime_types {
-		for available_mime in available_mimes.clone() {
-			match (target_mime.type_(), target_mime.subtype()) {
-				(available_type, available_subtype) if available_type == available_mime.type_() && available_subtype == available_mime.subtype() => return target_mime.clone(),
-				(mime::STAR, mime::STAR) => return available_mime.clone(),
-				(mime::STAR, available_subtype) if available_subtype == available_mime.subtype() => return available_mime.clone(),
-				(available_type, mime::STAR) if available_type == available_mime.type_() => return available_mime.clone(),
-				(_, _) => continue,
+/// Parse a single parameter value, which is either a token or a quoted string.
+fn parse_param_value(value: &str) -> Option<String> {
+	if let Some(quoted) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+		let mut unquoted = String::new();
+		let mut characters = quoted.chars();
+		while let Some(character) = characters.next() {
+			match character {
+				'\\' => unquoted.push(characters.next()?),
+				'"' => return None,
+				_ => unquoted.push(character),
 			}
 		}
+		return Some(unquoted);
 	}
+	is_token(value).then(|| value.to_string())
+}
 
-	return mime::APPLICATION_JSON
+fn parse_media_range(value: &str) -> Option<MediaRange> {
+	let mut parts = split_unquoted(value, ';').into_iter();
+	let (type_, subtype) = parts.next()?.trim().split_once('/')?;
+	if !is_token(type_) || !is_token(subtype) || (type_ == "*" && subtype != "*") {
+		return None;
+	}
+	let mut params = Vec::new();
+	let mut quality = 1000;
+	for param in parts {
+		let (name, value) = param.trim().split_once('=')?;
+		let name = name.trim_end().to_ascii_lowercase();
+		let value = parse_param_value(value.trim_start())?;
+		if !is_token(&name) {
+			return None;
+		}
+		// Anything after the weight is an extension to the `Accept` header, rather than a parameter of the type
+		if name == "q" {
+			quality = parse_quality(&value)?;
+			break;
+		}
+		params.push((name, value));
+	}
+	Some(MediaRange { type_: type_.to_ascii_lowercase(), subtype: subtype.to_ascii_lowercase(), params, quality })
+}
+
+/// Parse an `Accept` header as described in RFC 9110, skipping any media ranges which aren't valid.
+fn parse_accept(value: &str) -> Vec<MediaRange> {
+	split_unquoted(value, ',').into_iter()
+		.filter(|range| !range.trim().is_empty())
+		.filter_map(parse_media_range)
+		.collect()
+}
+
+/// None of the available types were acceptable to the client.
+/// Responds with `406 Not Acceptable`, listing the types which could have been asked for instead.
+#[derive(Debug)]
+pub struct NotAcceptable(Vec<Mime>);
+
+impl IntoResponse for NotAcceptable {
+	fn into_response(self) -> Response {
+		let supported: Vec<&str> = self.0.iter().map(|mime| mime.essence_str()).collect();
+		Response::builder()
+			.status(StatusCode::NOT_ACCEPTABLE)
+			.header(header::CONTENT_TYPE, "text/plain")
+			.body(format!("None of the requested media types are available.  Supported types: {}", supported.join(", ")).into())
+			.unwrap()
+	}
 }
 
-#[derive(Deserialize)]
+/// Choose which of the available types to respond with, based on the request's `Accept` header.
+///
+/// Each available type is weighted by the most specific media range which matches it, and the heaviest wins,
+/// with ties going to whichever was matched more specifically, then to the earliest in `available_mimes`.
+/// Any parameters given in the matching range (eg `text/csv;header=absent`) are included in the returned type.
+/// Without a usable `Accept` header, JSON is preferred if it's available.
+pub fn negotiate(headers: &HeaderMap, available_mimes: Vec<Mime>) -> Result<Mime, NotAcceptable> {
+	let ranges = headers.get(http::header::ACCEPT)
+		.and_then(|h| h.to_str().ok())
+		.map(parse_accept)
+		.unwrap_or_default();
+
+	if ranges.is_empty() {
+		return available_mimes.iter()
+			.find(|mime| *mime == &mime::APPLICATION_JSON)
+			.or(available_mimes.first())
+			.cloned()
+			.ok_or(NotAcceptable(available_mimes));
+	}
+
+	let mut best: Option<(u16, u8, &Mime, &MediaRange)> = None;
+	for available_mime in &available_mimes {
+		let Some(range) = ranges.iter()
+			.filter(|range| range.matches(available_mime))
+			.max_by_key(|range| range.specificity(available_mime)) else { continue };
+		if range.quality == 0 {
+			continue;
+		}
+		let specificity = range.specificity(available_mime);
+		if best.is_none_or(|(best_quality, best_specificity, _, _)| (range.quality, specificity) > (best_quality, best_specificity)) {
+			best = Some((range.quality, specificity, available_mime, range));
+		}
+	}
+
+	let Some((_, _, mime, range)) = best else {
+		return Err(NotAcceptable(available_mimes));
+	};
+	if range.params.is_empty() {
+		return Ok(mime.clone());
+	}
+	let params: String = range.params.iter()
+		.map(|(name, value)| match is_token(value) {
+			true => format!(";{}={}", name, value),
+			false => format!(";{}=\"{}\"", name, value.replace('\\', "\\\\").replace('"', "\\\"")),
+		})
+		.collect();
+	Ok(Mime::from_str(&format!("{}{}", mime.essence_str(), params)).unwrap_or_else(|_| mime.clone()))
+}
+
+#[derive(Deserialize, Default)]
 pub struct Params {
 	pub fields: Option<String>,
+	/// How lists are fitted into CSV cells.
+	#[serde(default)]
+	pub flatten: Flatten,
+	/// What to join list items with, when flattening with `Flatten::Join`.
+	pub separator: Option<String>,
+	#[serde(default)]
+	pub nulls: Nulls,
+	#[serde(default)]
+	pub defaults: Defaults,
+}
+
+/// Whether fields which aren't set are given as null, or left out.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Nulls {
+	#[default]
+	Include,
+	Omit,
+}
+
+/// Whether fields which aren't set, but which consumers are documented to assume a value for (see `Record::defaults`),
+/// are given as null, or with that value.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Defaults {
+	#[default]
+	Implicit,
+	Explicit,
+}
+
+/// How lists (and objects within them) are fitted into CSV, which only has room for one value per cell.
+/// Objects are always expanded into dotted columns, eg `public_ports.port`.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Flatten {
+	/// Join every item's value into one cell, eg `hosts` = `host1;host2`
+	#[default]
+	Join,
+	/// Give each item its own columns, numbered from zero, eg `hosts.0` and `hosts.1`
+	Columns,
+	/// Repeat the record once for each item, eg one row for `hosts` = `host1` and another for `hosts` = `host2`
+	Explode,
+}
+
+/// The most rows a CSV response can have when lists are exploded.
+const MAX_EXPLODED_ROWS: usize = 10_000;
+
+/// Used to join list items when no `separator` is given.  Chosen as it's rare in config values, and doesn't need quoting in CSV.
+const DEFAULT_SEPARATOR: &str = ";";
+
+/// Fields which aren't set are left empty, rather than given as `null`, which could be mistaken for a value.
+fn csv_cell(value: &Value) -> String {
+	match value {
+		Value::Null => String::new(),
+		Value::String(s) => s.clone(),
+		_ => value.to_string(),
+	}
+}
+
+/// Repeat a value once for each combination of items from the lists within it, leaving no lists.
+/// Empty lists are left out altogether, rather than removing the value they're in.
+fn explode(value: Value) -> Vec<Value> {
+	match value {
+		Value::Array(items) => items.into_iter().flat_map(explode).collect(),
+		Value::Object(map) => {
+			let mut variants = vec![Map::new()];
+			for (key, value) in map {
+				let values = explode(value);
+				if values.is_empty() {
+					continue;
+				}
+				let key = &key;
+				variants = variants.into_iter()
+					.flat_map(|variant| values.iter().map(move |value| {
+						let mut variant = variant.clone();
+						variant.insert(key.clone(), value.clone());
+						variant
+					}))
+					.collect();
+			}
+			variants.into_iter().map(Value::Object).collect()
+		},
+		other => vec![other],
+	}
+}
+
+/// How many values `explode` would give, without making them.
+fn explode_count(value: &Value) -> usize {
+	match value {
+		Value::Array(items) => items.iter().map(explode_count).fold(0, usize::saturating_add),
+		Value::Object(map) => map.values().map(explode_count).filter(|count| *count > 0).fold(1, usize::saturating_mul),
+		_ => 1,
+	}
 }
 
-fn filter_fields(value: Value, allowed: &Option<HashSet<String>>) -> Value {
-	if let Some(fields) = allowed {
-		match value {
-			Value::Object(map) => {
-				let filtered = map
-					.into_iter()
-					.filter(|(k, _)| fields.contains(k))
-					.map(|(k, v)| (k, filter_fields(v, allowed)))
-					.collect::<Map<_, _>>();
-				Value::Object(filtered)
+/// Flatten a value into CSV cells, each keyed by the dotted path to it from `path`.
+fn flatten_into(path: String, value: Value, flatten: Flatten, separator: &str, cells: &mut Vec<(String, String)>) {
+	let child_path = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+	match value {
+		Value::Object(map) => for (key, value) in map {
+			flatten_into(child_path(&key), value, flatten, separator, cells);
+		},
+		Value::Array(items) if flatten == Flatten::Columns => for (index, value) in items.into_iter().enumerate() {
+			flatten_into(child_path(&index.to_string()), value, flatten, separator, cells);
+		},
+		Value::Array(items) => {
+			// Flatten each item separately, then join together the values for each column
+			let mut columns: Vec<(String, Vec<String>)> = Vec::new();
+			for item in items {
+				let mut item_cells = Vec::new();
+				flatten_into(path.clone(), item, flatten, separator, &mut item_cells);
+				for (column, cell) in item_cells {
+					match columns.iter_mut().find(|(existing, _)| *existing == column) {
+						Some((_, values)) => values.push(cell),
+						None => columns.push((column, vec![cell])),
+					}
+				}
 			}
-			Value::Array(vec) => Value::Array(
-				vec.into_iter()
-					.map(|v| filter_fields(v, allowed))
-					.collect(),
-			),
-			other => other,
+			cells.extend(columns.into_iter().map(|(column, values)| (column, values.join(separator))));
+		},
+		other => cells.push((if path.is_empty() { "value".to_string() } else { path }, csv_cell(&other))),
+	}
+}
+
+/// Whether a column belongs to the given field, either because it is the field, or is nested within it.
+/// The numbered segments of columns from `Flatten::Columns` are skipped, as fields select within every item of a list.
+fn column_in_field(column: &str, field: &str) -> bool {
+	let mut column = column.split('.').filter(|segment| segment.parse::<usize>().is_err());
+	field.split('.').all(|segment| column.next() == Some(segment))
+}
+
+/// Respond with each record as a row of CSV (or several, if lists are exploded).
+/// Columns are in the order given by the `fields` param, or otherwise the order they're first found in.
+/// The header row is omitted if the negotiated type has `header=absent`.
+fn csv_response(mime: &Mime, params: &Params, selection: Option<&FieldSelection>, records: Vec<Value>) -> Response {
+	let print_csv_header = mime.get_param("header").map(|n| n.as_str()).unwrap_or("present") != "absent";
+	let separator = params.separator.as_deref().unwrap_or(DEFAULT_SEPARATOR);
+
+	if params.flatten == Flatten::Explode {
+		// Exploding several lists gives a row for every combination of their items, which soon adds up
+		let row_count = records.iter().map(explode_count).fold(0, usize::saturating_add);
+		if row_count > MAX_EXPLODED_ROWS {
+			let message = format!("Exploding lists would give {} rows, more than the limit of {}; use `fields` to select fewer lists", row_count, MAX_EXPLODED_ROWS);
+			return (StatusCode::BAD_REQUEST, message).into_response();
+		}
+	}
+	let mut rows: Vec<Vec<(String, String)>> = Vec::new();
+	for value in records {
+		let values = match params.flatten {
+			Flatten::Explode => explode(value),
+			_ => vec![value],
+		};
+		for value in values {
+			let mut cells = Vec::new();
+			flatten_into(String::new(), value, params.flatten, separator, &mut cells);
+			rows.push(cells);
 		}
+	}
+
+	let mut found_columns: Vec<&String> = Vec::new();
+	for (column, _) in rows.iter().flatten() {
+		if !found_columns.contains(&column) {
+			found_columns.push(column);
+		}
+	}
+	let given_fields = selection.map(FieldSelection::includes).unwrap_or_default();
+	let columns: Vec<String> = if given_fields.is_empty() {
+		found_columns.into_iter().cloned().collect()
 	} else {
-		// No filtering requested — return unchanged
-		value
+		// Each given field becomes all the columns within it, or a single empty column if there are none
+		given_fields.into_iter().flat_map(|field| {
+			let within: Vec<String> = found_columns.iter()
+				.filter(|column| column_in_field(column, &field))
+				.map(|column| column.to_string())
+				.collect();
+			if within.is_empty() { vec![field] } else { within }
+		}).collect()
+	};
+
+	let mut w = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
+	if print_csv_header {
+		w.write_record(&columns).unwrap();
+	}
+	for cells in &rows {
+		w.write_record(columns.iter().map(|column| {
+			cells.iter().find(|(cell_column, _)| cell_column == column).map(|(_, cell)| cell.as_str()).unwrap_or_default()
+		})).unwrap();
+	}
+
+	let csv_output = String::from_utf8(w.into_inner().unwrap()).unwrap();
+	Response::builder()
+		.status(StatusCode::OK)
+		.header(header::CONTENT_TYPE, "text/csv")
+		.body(csv_output.into())
+		.unwrap()
+}
+
+fn yaml_response(value: Value) -> Response {
+	let yaml_value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&serde_json::to_string(&value).unwrap()).unwrap();
+	Yaml(yaml_value).into_response()
+}
+
+/// Replace a record's null top-level fields with their default, as given by `Record::defaults`.
+pub fn fill_defaults(record: &mut Value, defaults: &[(&'static str, Value)]) {
+	let Value::Object(map) = record else {
+		return;
+	};
+	for (field, default) in defaults {
+		if let Some(value @ Value::Null) = map.get_mut(*field) {
+			*value = default.clone();
+		}
 	}
 }
 
-/// Filter a serde_json::Value object and produce a `Vec<String>` suitable for CSV rows.
-fn filter_fields_csv(value: Value, allowed: &Option<HashSet<String>>, order: &Option<Vec<String>>) -> Vec<String> {
+/// Remove every null field from every object within a value.
+pub fn omit_nulls(value: &mut Value) {
 	match value {
 		Value::Object(map) => {
-			let keys: Vec<String> = if let Some(order) = order {
-				order.clone()
-			} else if let Some(allowed) = allowed {
-				map.keys().filter(|k| allowed.contains(*k)).cloned().collect()
-			} else {
-				map.keys().cloned().collect()
-			};
-
-			keys.iter().map(|k| {
-				map.get(k).map(|v| match v {
-					Value::String(s) => s.clone(),
-					_ => v.to_string(),
-				}).unwrap_or_default()
-			}).collect()
+			map.retain(|_, value| !value.is_null());
+			map.values_mut().for_each(omit_nulls);
+		},
+		Value::Array(items) => items.iter_mut().for_each(omit_nulls),
+		_ => {},
+	}
+}
+
+/// Shape each record as the query params ask: filling in defaults, trimming it down to the fields selected
+/// by the `fields` param, then leaving out nulls.
+/// Responds with `400 Bad Request` if `fields` names any fields which records of type `R` don't have.
+fn prepare_records<R: Record>(params: &Params, selection: Option<&FieldSelection>, mut records: Vec<Value>) -> Result<Vec<Value>, UnknownFields> {
+	if params.defaults == Defaults::Explicit {
+		let defaults = R::defaults();
+		records.iter_mut().for_each(|record| fill_defaults(record, &defaults));
+	}
+	if let Some(selection) = selection {
+		let unknown = selection.unknown_fields(&R::fields());
+		if !unknown.is_empty() {
+			return Err(UnknownFields(unknown));
 		}
-		Value::Array(arr) => arr.iter().map(|v| match v {
-			Value::String(s) => s.clone(),
-			_ => v.to_string(),
-		}).collect(),
-		other => vec![match other {
-			Value::String(s) => s,
-			_ => other.to_string(),
-		}],
+		records = records.into_iter().map(|record| selection.apply(record)).collect();
 	}
+	if params.nulls == Nulls::Omit {
+		records.iter_mut().for_each(omit_nulls);
+	}
+	Ok(records)
 }
 
 /// Respond with a single item (not wrapped in an array), or 404 if None.
-/// Supports the same content negotiation and `fields` filtering as `negotiate_response`.
-pub fn negotiate_response_single(
+/// Supports the same content negotiation and `fields` filtering as `negotiate_response`, with CSV giving a single row.
+pub fn negotiate_response_single<T: serde::Serialize + Record>(
 	headers: &HeaderMap,
 	Query(params): Query<Params>,
-	item: Option<serde_json::Value>,
+	item: Option<T>,
 ) -> Response {
-	let Some(value) = item else {
+	let Some(item) = item else {
 		return Response::builder()
 			.status(StatusCode::NOT_FOUND)
 			.header(header::CONTENT_TYPE, "text/plain")
@@ -117,30 +466,27 @@ pub fn negotiate_response_single(
 			.unwrap();
 	};
 
-	let fields: Option<HashSet<String>> = params.fields.as_ref().map(|s| {
-		s.split(',')
-			.map(|s| s.trim().to_string())
-			.filter(|s| !s.is_empty())
-			.collect()
-	});
+	let selection = params.fields.as_deref().map(FieldSelection::parse);
 
 	let available_mimes = vec![
 		mime::APPLICATION_JSON,
 		Mime::from_str("application/x-yaml").unwrap(),
+		Mime::from_str("text/csv").unwrap(),
 	];
 
-	let mime = negotiate(headers, available_mimes);
+	let mime = match negotiate(headers, available_mimes) {
+		Ok(mime) => mime,
+		Err(not_acceptable) => return not_acceptable.into_response(),
+	};
+	let records = match prepare_records::<T>(&params, selection.as_ref(), vec![serde_json::to_value(item).unwrap()]) {
+		Ok(records) => records,
+		Err(unknown_fields) => return unknown_fields.into_response(),
+	};
 
 	match mime.essence_str() {
-		"application/x-yaml" => {
-			let filtered = filter_fields(value, &fields);
-			let yaml_value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&serde_json::to_string(&filtered).unwrap()).unwrap();
-			Yaml(yaml_value).into_response()
-		},
-		"application/json" | _ => {
-			let filtered = filter_fields(value, &fields);
-			Json(filtered).into_response()
-		},
+		"application/x-yaml" => yaml_response(records.into_iter().next().unwrap()),
+		"text/csv" => csv_response(&mime, &params, selection.as_ref(), records),
+		_ => Json(records.into_iter().next().unwrap()).into_response(),
 	}
 }
 
@@ -150,15 +496,9 @@ pub fn negotiate_response<T>(
 	data: T,
 ) -> Response
 where
-	T: serde::Serialize + Clone + std::iter::IntoIterator<Item: serde::Serialize>,
+	T: serde::Serialize + std::iter::IntoIterator<Item: serde::Serialize + Record>,
 {
-	// Parse "fields" query param into a HashSet
-	let fields: Option<HashSet<String>> = params.fields.as_ref().map(|s| {
-		s.split(',')
-			.map(|s| s.trim().to_string())
-			.filter(|s| !s.is_empty())
-			.collect()
-	});
+	let selection = params.fields.as_deref().map(FieldSelection::parse);
 
 	let available_mimes = vec![
 		mime::APPLICATION_JSON,
@@ -166,63 +506,24 @@ where
 		Mime::from_str("text/csv").unwrap(),
 	];
 
-	let mime = negotiate(headers, available_mimes);
-
-	match mime.essence_str() {
-		"application/x-yaml" => {
-			let value = serde_json::to_value(data.clone()).unwrap();
-			let filtered = filter_fields(value, &fields);
-			let yaml_value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&serde_json::to_string(&filtered).unwrap()).unwrap();
-			Yaml(yaml_value).into_response()
-		},
-		"text/csv" => {
-			let print_csv_header = mime.get_param("header").map(|n| n.as_str()).unwrap_or("present") != "absent";
-			let mut w = csv::WriterBuilder::new().has_headers(false).from_writer(vec![]);
-
-			// If a list of fields has been given, use them for field_order
-			let given_field_order: Option<Vec<String>> = params.fields.as_ref().map(|s| {
-				s.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
-			});
-			let field_order: Vec<String> = if let Some(ref order) = given_field_order {
-				order.clone()
-			// Otherwise, try to infer fields from the first record
-			} else if let Some(record) = data.clone().into_iter().next() {
-				let value = serde_json::to_value(record).unwrap();
-				if let Value::Object(map) = value {
-					map.iter()
-						.filter(|(_, v)| !matches!(v, Value::Array(_)))
-						.map(|(k, _)| k.clone())
-						.collect()
-				} else {
-					vec!["value".to_string()]
-				}
-			// If there's no records, then the fields names are kinda moot
-			} else {
-				vec![]
-			};
-
-			if print_csv_header {
-				w.write_record(&field_order).unwrap();
-			}
+	let mime = match negotiate(headers, available_mimes) {
+		Ok(mime) => mime,
+		Err(not_acceptable) => return not_acceptable.into_response(),
+	};
+	if mime.essence_str() == "application/json" && selection.is_none() && params.nulls == Nulls::Include && params.defaults == Defaults::Implicit {
+		return Json(data).into_response();
+	}
 
-			for record in data {
-				let value = serde_json::to_value(record).unwrap();
-				let row = filter_fields_csv(value, &fields, &Some(field_order.clone()));
-				w.write_record(row).unwrap();
-			}
+	let records: Vec<Value> = data.into_iter().map(|record| serde_json::to_value(record).unwrap()).collect();
+	let records = match prepare_records::<T::Item>(&params, selection.as_ref(), records) {
+		Ok(records) => records,
+		Err(unknown_fields) => return unknown_fields.into_response(),
+	};
 
-			let csv_output = String::from_utf8(w.into_inner().unwrap()).unwrap();
-			Response::builder()
-				.status(StatusCode::OK)
-				.header(header::CONTENT_TYPE, "text/csv")
-				.body(csv_output.into())
-				.unwrap()
-		},
-		"application/json"|_ => {
-			let value = serde_json::to_value(data.clone()).unwrap();
-			let filtered = filter_fields(value, &fields);
-			Json(filtered).into_response()
-		},
+	match mime.essence_str() {
+		"application/x-yaml" => yaml_response(Value::Array(records)),
+		"text/csv" => csv_response(&mime, &params, selection.as_ref(), records),
+		_ => Json(records).into_response(),
 	}
 }
 
@@ -240,11 +541,11 @@ mod negotiate_tests {
 			Mime::from_str("text/html").unwrap(),
 			Mime::from_str("application/json").unwrap(),
 		];
-		let negotiated = negotiate(&headers, available);
+		let negotiated = negotiate(&headers, available).unwrap();
 		assert_eq!(negotiated.essence_str(), "application/json");
 
 		let available = vec![Mime::from_str("application/xml").unwrap()];
-		let negotiated = negotiate(&headers, available);
+		let negotiated = negotiate(&headers, available).unwrap();
 		assert_eq!(negotiated.essence_str(), "application/xml");
 	}
 
@@ -257,7 +558,7 @@ mod negotiate_tests {
 			Mime::from_str("text/html").unwrap(),
 			Mime::from_str("text/csv").unwrap(),
 		];
-		let negotiated = negotiate(&headers, available);
+		let negotiated = negotiate(&headers, available).unwrap();
 		assert_eq!(negotiated.essence_str(), "text/csv");
 
 		let header_parameter = negotiated.get_param("header").unwrap().as_str();
@@ -273,7 +574,7 @@ mod negotiate_tests {
 			Mime::from_str("text/html").unwrap(),
 			Mime::from_str("application/json").unwrap(),
 		];
-		let negotiated = negotiate(&headers, available);
+		let negotiated = negotiate(&headers, available).unwrap();
 		assert_eq!(negotiated.essence_str(), "text/html");
 	}
 	#[test]
@@ -284,9 +585,86 @@ mod negotiate_tests {
 			Mime::from_str("text/html").unwrap(),
 			Mime::from_str("application/json").unwrap(),
 		];
-		let negotiated = negotiate(&headers, available);
+		let negotiated = negotiate(&headers, available).unwrap();
 		assert_eq!(negotiated.essence_str(), "application/json");
 	}
+
+	fn accept(value: &str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert(http::header::ACCEPT, value.parse().unwrap());
+		headers
+	}
+
+	fn available() -> Vec<Mime> {
+		vec![
+			mime::APPLICATION_JSON,
+			Mime::from_str("application/x-yaml").unwrap(),
+			Mime::from_str("text/csv").unwrap(),
+		]
+	}
+
+	#[test]
+	fn more_specific_ranges_take_precedence() {
+		// text/csv is excluded by its own range, even though text/* and */* would accept it
+		let negotiated = negotiate(&accept("*/*;q=0.1, text/*;q=0.5, text/csv;q=0"), available()).unwrap();
+		assert_eq!(negotiated.essence_str(), "application/json");
+
+		// An exact match beats a wildcard of the same weight, whichever order they're given in
+		let negotiated = negotiate(&accept("*/*, application/x-yaml"), available()).unwrap();
+		assert_eq!(negotiated.essence_str(), "application/x-yaml");
+	}
+
+	#[test]
+	fn parameters_the_type_lacks_dont_outrank_the_bare_type() {
+		// Refusing headerless CSV doesn't refuse CSV altogether, whichever order they're given in
+		for header in ["text/csv;header=absent;q=0, text/csv", "text/csv, text/csv;header=absent;q=0"] {
+			let negotiated = negotiate(&accept(header), available()).unwrap();
+			assert_eq!(negotiated.essence_str(), "text/csv", "{header}");
+			assert!(negotiated.get_param("header").is_none(), "{header}");
+		}
+
+		// But the variant can still be asked for on its own
+		let negotiated = negotiate(&accept("text/csv;header=absent, application/json;q=0.5"), available()).unwrap();
+		assert_eq!(negotiated.get_param("header").unwrap().as_str(), "absent");
+
+		// Parameters which the type does have make for a more specific match than the bare type
+		let available = vec![Mime::from_str("text/plain;charset=utf-8").unwrap()];
+		let negotiated = negotiate(&accept("text/plain;q=0, text/plain;charset=utf-8"), available);
+		assert_eq!(negotiated.unwrap().essence_str(), "text/plain");
+	}
+
+	#[test]
+	fn weights_rank_matching_types() {
+		let negotiated = negotiate(&accept("application/json;q=0.2, application/x-yaml;q=0.3"), available()).unwrap();
+		assert_eq!(negotiated.essence_str(), "application/x-yaml");
+	}
+
+	#[test]
+	fn commas_and_semicolons_in_quoted_parameters_are_ignored() {
+		let negotiated = negotiate(&accept("text/csv;note=\"a, b; c\";header=absent, application/json;q=0.5"), available()).unwrap();
+		assert_eq!(negotiated.essence_str(), "text/csv");
+		assert_eq!(negotiated.get_param("header").unwrap().as_str(), "absent");
+	}
+
+	#[test]
+	fn invalid_weights_are_ignored_without_panicking() {
+		let negotiated = negotiate(&accept("text/csv;q=NaN, text/csv;q=2, application/x-yaml;q=0.5"), available()).unwrap();
+		assert_eq!(negotiated.essence_str(), "application/x-yaml");
+	}
+
+	#[test]
+	fn nothing_acceptable_is_406() {
+		let response = negotiate(&accept("text/html, application/json;q=0"), available()).unwrap_err().into_response();
+		assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+	}
+
+	#[test]
+	fn accept_header_is_parsed() {
+		assert_eq!(parse_accept("text/html;level=1;q=0.5;ext=x, */*; q=0, invalid, text/*;q=1.5"), vec![
+			MediaRange { type_: "text".into(), subtype: "html".into(), params: vec![("level".into(), "1".into())], quality: 500 },
+			MediaRange { type_: "*".into(), subtype: "*".into(), params: vec![], quality: 0 },
+		]);
+	}
 }
 
 #[cfg(test)]
@@ -304,6 +682,12 @@ mod negotiate_response_tests {
 		c: bool,
 	}
 
+	impl Record for TestRecord {
+		fn fields() -> Vec<&'static str> {
+			vec!["a", "b", "c"]
+		}
+	}
+
 	fn make_data() -> Vec<TestRecord> {
 		vec![
 			TestRecord { a: 1, b: "x".to_string(), c: true },
@@ -323,7 +707,7 @@ mod negotiate_response_tests {
 		let mut headers = HeaderMap::new();
 		headers.insert(http::header::ACCEPT, "application/json".parse().unwrap());
 
-		let resp = negotiate_response(&headers, Query(Params { fields: None }), make_data());
+		let resp = negotiate_response(&headers, Query(Params::default()), make_data());
 		let body = body_string(resp).await;
 		let parsed: Value = serde_json::from_str(&body).unwrap();
 		assert_eq!(parsed.as_array().unwrap().len(), 2);
@@ -339,7 +723,7 @@ mod negotiate_response_tests {
 
 		let resp = negotiate_response(
 			&headers,
-			Query(Params { fields: Some("a,c".to_string()) }),
+			Query(Params { fields: Some("a,c".to_string()), ..Default::default() }),
 			make_data()
 		);
 		let body = body_string(resp).await;
@@ -355,7 +739,7 @@ mod negotiate_response_tests {
 		let mut headers = HeaderMap::new();
 		headers.insert(http::header::ACCEPT, "application/x-yaml".parse().unwrap());
 
-		let resp = negotiate_response(&headers, Query(Params { fields: None }), make_data());
+		let resp = negotiate_response(&headers, Query(Params::default()), make_data());
 		let body = body_string(resp).await;
 		assert!(body.contains("a: 1"));
 		assert!(body.contains("b: x"));
@@ -369,7 +753,7 @@ mod negotiate_response_tests {
 
 		let resp = negotiate_response(
 			&headers,
-			Query(Params { fields: Some("b,c".to_string()) }),
+			Query(Params { fields: Some("b,c".to_string()), ..Default::default() }),
 			make_data()
 		);
 		let body = body_string(resp).await;
@@ -385,7 +769,7 @@ mod negotiate_response_tests {
 
 		let resp = negotiate_response(
 			&headers,
-			Query(Params { fields: Some("b,a".to_string()) }),
+			Query(Params { fields: Some("b,a".to_string()), ..Default::default() }),
 			make_data()
 		);
 		let body = body_string(resp).await;
@@ -402,7 +786,7 @@ mod negotiate_response_tests {
 
 		let resp = negotiate_response(
 			&headers,
-			Query(Params { fields: Some("b,a".to_string()) }),
+			Query(Params { fields: Some("b,a".to_string()), ..Default::default() }),
 			make_data()
 		);
 		let body = body_string(resp).await;
@@ -417,7 +801,7 @@ mod negotiate_response_tests {
 		let mut headers = HeaderMap::new();
 		headers.insert(http::header::ACCEPT, "text/csv".parse().unwrap());
 
-		let resp = negotiate_response(&headers, Query(Params { fields: None }), make_data());
+		let resp = negotiate_response(&headers, Query(Params::default()), make_data());
 		let body = body_string(resp).await;
 		let mut lines = body.lines();
 		let header = lines.next().unwrap();
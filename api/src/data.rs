@@ -1,12 +1,20 @@
 use serde_yaml_ng;
 use serde::{Serialize, Deserialize, Deserializer};
+use serde::de::DeserializeOwned;
 use serde_json::Value;
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::vec::Vec;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use crate::registry::{AnyCollection, Collection, Entity};
+use crate::source::ConfigSource;
+use crate::source_map::{self, SourceMap};
+use crate::validation::{Issue, ValidationReport};
 
 fn default_true() -> bool { true }
-fn default_full_snapshot() -> String { "full-snapshot".to_string() }
 
 /// Custom deserializer that rejects port 0 (valid TCP ports are 1–65535).
 fn validate_port<'de, D>(deserializer: D) -> Result<u16, D::Error>
@@ -55,11 +63,102 @@ pub struct System {
 	pub public_ports: Vec<PublicPort>,
 }
 
+/// How hard a volume's data is to recreate if lost.
+///
+/// The canonical source of truth for this set is `lucos_backups/src/effort_labels.yaml` —
+/// lucos_backups looks each volume's `recreate_effort` up against it unguarded, so an
+/// unrecognised value crashes host-tracking for an entire host in production.  This is a
+/// deliberate duplication: if lucos_backups ever adds or renames an effort label, this enum
+/// must be updated in lockstep.  See lucas42/lucos_configy#221.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecreateEffort {
+	Small,
+	Considerable,
+	Huge,
+	Automatic,
+	Tolerable,
+	Remote,
+	Unknown,
+}
+
+impl RecreateEffort {
+	pub const ALL: [RecreateEffort; 7] = [
+		RecreateEffort::Small,
+		RecreateEffort::Considerable,
+		RecreateEffort::Huge,
+		RecreateEffort::Automatic,
+		RecreateEffort::Tolerable,
+		RecreateEffort::Remote,
+		RecreateEffort::Unknown,
+	];
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			RecreateEffort::Small => "small",
+			RecreateEffort::Considerable => "considerable",
+			RecreateEffort::Huge => "huge",
+			RecreateEffort::Automatic => "automatic",
+			RecreateEffort::Tolerable => "tolerable",
+			RecreateEffort::Remote => "remote",
+			RecreateEffort::Unknown => "unknown",
+		}
+	}
+}
+
+/// Which backup mechanism lucos_backups uses for a volume.
+///
+/// The canonical source of truth is `lucos_backups` (ADR-0002).  A value it doesn't recognise
+/// would silently fall back to full-snapshot, quietly defeating the opt-in, so if lucos_backups
+/// adds a new strategy, update this enum in lockstep.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupStrategy {
+	/// The daily full tar+scp.
+	#[default]
+	FullSnapshot,
+	/// rsync --link-dest hardlink-rotated snapshots, for large append-mostly media volumes.
+	Incremental,
+}
+
+impl BackupStrategy {
+	pub const ALL: [BackupStrategy; 2] = [
+		BackupStrategy::FullSnapshot,
+		BackupStrategy::Incremental,
+	];
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			BackupStrategy::FullSnapshot => "full-snapshot",
+			BackupStrategy::Incremental => "incremental",
+		}
+	}
+}
+
+/// Which family of shell utilities a host has, for scripts which run on it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellFlavour {
+	Gnu,
+	Busybox,
+}
+
+impl ShellFlavour {
+	pub const ALL: [ShellFlavour; 2] = [
+		ShellFlavour::Gnu,
+		ShellFlavour::Busybox,
+	];
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ShellFlavour::Gnu => "gnu",
+			ShellFlavour::Busybox => "busybox",
+		}
+	}
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Volume {
 	pub id: Option<String>, // This is optional because the raw yaml specifies it as than key, rather than as an attribute
 	pub description: Option<String>,
-	pub recreate_effort: Option<String>,
+	pub recreate_effort: Option<RecreateEffort>,
 	#[serde(default)]
 	pub skip_backup: bool,
 	#[serde(default)]
@@ -70,30 +169,89 @@ pub struct Volume {
 	// snapshots (ADR-0002 in lucos_backups). A per-volume opt-in, not an estate
 	// default. Always serialised with an explicit value so consumers never have
 	// to know the default themselves.
-	#[serde(default = "default_full_snapshot")]
-	pub backup_strategy: String,
+	#[serde(default)]
+	pub backup_strategy: BackupStrategy,
+}
+
+/// Where a host is in its lifecycle.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HostStatus {
+	/// In service.
+	#[default]
+	Active,
+	/// Temporarily out of service (eg powered off or disconnected), but expected to return.
+	Inactive,
+	/// Permanently out of service.  Kept in the config for the record only.
+	Decommissioned,
+}
+
+impl HostStatus {
+	pub const ALL: [HostStatus; 3] = [
+		HostStatus::Active,
+		HostStatus::Inactive,
+		HostStatus::Decommissioned,
+	];
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			HostStatus::Active => "active",
+			HostStatus::Inactive => "inactive",
+			HostStatus::Decommissioned => "decommissioned",
+		}
+	}
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Host {
 	pub id: Option<String>, // This is optional because the raw yaml specifies it as than key, rather than as an attribute
+	#[serde(default)]
+	pub status: HostStatus, // Hosts which aren't active are left out of the host-based endpoints by default
 	pub domain: Option<String>,
-	pub ipv4: Option<String>, // The primary IPv4 address for this host
-	pub ipv6: Option<String>, // The primary IPv6 address for this host
-	pub ipv4_nat: Option<String>, // An IPv4 address that may forward ports to the host.  For use from legacy networks which don't support IPv6.
+	pub ipv4: Option<Ipv4Addr>, // The primary IPv4 address for this host
+	pub ipv6: Option<Ipv6Addr>, // The primary IPv6 address for this host
+	pub ipv4_nat: Option<Ipv4Addr>, // An IPv4 address that may forward ports to the host.  For use from legacy networks which don't support IPv6.
+	#[serde(skip_deserializing)]
+	pub ipv4_private: Option<bool>, // Derived: whether ipv4 is private (RFC1918), loopback or link-local.  Null if there's no ipv4.
+	#[serde(skip_deserializing)]
+	pub ipv6_private: Option<bool>, // Derived: whether ipv6 is a unique local (ULA), loopback or link-local address.  Null if there's no ipv6.
+	#[serde(skip_deserializing)]
+	pub lan_only: bool, // Derived: whether the host has addresses, but none of them are reachable from the public internet
 	#[serde(default)]
 	pub serves_http: bool,
 	pub ssh_gateway: Option<String>,   // hostname of a host to use as ProxyJump when connecting
 	pub backup_root: Option<String>,   // backup storage root path; lucos_backups defaults to /srv/backups/
 	#[serde(default)]
 	pub is_storage_only: bool,         // skip this host from the backup source loop in lucos_backups
-	pub shell_flavour: Option<String>, // "gnu" (default) or "busybox"
+	pub shell_flavour: Option<ShellFlavour>, // lucos_backups assumes gnu when not set
 	#[serde(default = "default_true")]
 	pub can_reach_external_services: bool, // whether this host can wget/curl from public HTTPS (e.g. GitHub codeload); defaults true
 	#[serde(default)]
 	pub firewall_enforce: bool, // whether lucos_firewall is in enforce mode on this host (false = dry-run); defaults false
 }
 
+/// Whether an IPv4 address can't be reached from the public internet: private (RFC 1918), loopback or link-local.
+fn is_private_ipv4(ip: Ipv4Addr) -> bool {
+	ip.is_private() || ip.is_loopback() || ip.is_link_local()
+}
+
+/// Whether an IPv6 address can't be reached from the public internet: unique local (ULA), loopback or link-local.
+fn is_private_ipv6(ip: Ipv6Addr) -> bool {
+	ip.is_unique_local() || ip.is_loopback() || ip.is_unicast_link_local()
+}
+
+impl Host {
+	/// Work out the fields which are derived from the host's addresses, rather than given in the YAML.
+	pub(crate) fn set_derived_fields(&mut self) {
+		self.ipv4_private = self.ipv4.map(is_private_ipv4);
+		self.ipv6_private = self.ipv6.map(is_private_ipv6);
+		let has_address = self.ipv4.is_some() || self.ipv6.is_some() || self.ipv4_nat.is_some();
+		let has_public_address = self.ipv4_private == Some(false)
+			|| self.ipv6_private == Some(false)
+			|| self.ipv4_nat.is_some_and(|ip| !is_private_ipv4(ip));
+		self.lan_only = has_address && !has_public_address;
+	}
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Component {
 	pub id: Option<String>, // This is optional because the raw yaml specifies it as than key, rather than as an attribute
@@ -108,67 +266,347 @@ pub struct Script {
 	pub unsupervised_agent_code: bool,
 }
 
+/// How `Data::from_dir` treats keys in the YAML which don't correspond to any field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strictness {
+	/// Unknown keys cause the load to fail (the default).
+	#[default]
+	Strict,
+	/// Unknown keys are logged as warnings and otherwise ignored.
+	Lenient,
+}
+
 // The format of data to expose publically
 pub struct Data {
-	systems: Vec<System>,
-	volumes: Vec<Volume>,
-	hosts: Vec<Host>,
-	components: Vec<Component>,
-	scripts: Vec<Script>,
+	systems: Collection<System>,
+	volumes: Collection<Volume>,
+	hosts: Collection<Host>,
+	components: Collection<Component>,
+	scripts: Collection<Script>,
+	warnings: Vec<Issue>,
+	/// A hash of the loaded entities, which only changes when their content does.
+	version: String,
+	loaded_at: SystemTime,
 }
 
+/// The config files for the given kind of entity (eg "systems"), relative to the config directory.
+///
+/// Entities can be kept in a single file (eg `systems.yaml`), and/or split across any number of files
+/// in a directory of the same name (eg `systems/lucos_mail.yaml`).  Files are returned in a stable order:
+/// the single file first, then the directory's files alphabetically.
+pub fn config_files<S: ConfigSource + ?Sized>(source: &S, kind: &str) -> Vec<String> {
+	let mut files = Vec::new();
+	let single_file = format!("{}.yaml", kind);
+	if source.is_file(&single_file) {
+		files.push(single_file);
+	}
+	let mut split_files: Vec<String> = source.list_files(kind)
+		.into_iter()
+		.filter(|name| name.ends_with(".yaml"))
+		.map(|name| format!("{}/{}", kind, name))
+		.collect();
+	split_files.sort();
+	files.extend(split_files);
+	files
+}
 
-impl Data {
-	pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Data, Box<dyn std::error::Error>> {
-		let mut data = Data {
-			systems: vec![],
-			volumes: vec![],
-			hosts: vec![],
-			components: vec![],
-			scripts: vec![],
-		};
-		let systems_file = std::fs::File::open(path.as_ref().join("systems.yaml"))?;
-		let mut raw_systems: HashMap<String, System> = serde_yaml_ng::from_reader(systems_file)?;
-		for (id, system) in raw_systems.iter_mut() {
-			system.id = Some(id.to_string());
-			data.systems.push(system.clone());
+/// Reads each config file in turn, accumulating every problem found rather than stopping at the first.
+struct Loader<'a> {
+	source: &'a dyn ConfigSource,
+	strictness: Strictness,
+	errors: Vec<Issue>,
+	warnings: Vec<Issue>,
+	/// Where everything is in each file loaded so far, keyed by file name.
+	source_maps: HashMap<String, SourceMap>,
+	/// Which file each entity was loaded from, keyed by kind and then id.
+	origins: HashMap<String, HashMap<String, String>>,
+	/// Ids of entities which couldn't be deserialised, keyed by kind.
+	/// They've already been reported, so references to them shouldn't be reported again.
+	unparsed: HashMap<String, HashSet<String>>,
+}
+
+/// Build an issue about the entity with the given id, located using the path to the offending node.
+fn entity_issue(file_name: &str, source_map: &SourceMap, id: &str, path: &[String], message: String) -> Issue {
+	let full_path: Vec<&str> = std::iter::once(id).chain(path.iter().map(String::as_str)).collect();
+	let location = source_map.locate(&full_path);
+	Issue {
+		file: file_name.to_string(),
+		line: location.map(|l| l.line),
+		column: location.map(|l| l.column),
+		entity: Some(id.to_string()),
+		message,
+	}
+}
+
+impl Loader<'_> {
+	/// Load every entity of the given kind, with their ids and derived fields set.
+	fn load_collection<T: Entity>(&mut self) -> Collection<T> {
+		let items = self.load::<T>(T::KIND).into_iter().map(|(id, mut item)| {
+			item.set_id(id);
+			item.after_load();
+			item
+		}).collect();
+		Collection::new(items)
+	}
+
+	/// Load every entity of the given kind (eg "systems") from all its config files.
+	/// An id defined in more than one file is an error; the first definition is the one kept.
+	fn load<T: DeserializeOwned>(&mut self, kind: &str) -> Vec<(String, T)> {
+		let files = config_files(self.source, kind);
+		if files.is_empty() {
+			self.errors.push(Issue {
+				file: format!("{}.yaml", kind),
+				line: None,
+				column: None,
+				entity: None,
+				message: format!("No {}.yaml file or {}/ directory found", kind, kind),
+			});
+			return vec![];
 		}
-		data.systems.sort_by(|d1, d2| d1.id.cmp(&d2.id));
+		let mut entities = Vec::new();
+		for file_name in files {
+			let mut file_entities = self.load_file::<T>(kind, &file_name);
+			file_entities.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
+			for (id, entity) in file_entities {
+				let origins = self.origins.entry(kind.to_string()).or_default();
+				if let Some(first_file) = origins.get(&id) {
+					let first_line = self.source_maps.get(first_file).and_then(|map| map.locate(&[&id])).map(|l| l.line).unwrap_or_default();
+					let message = format!("duplicate id (also defined in {} on line {})", first_file, first_line);
+					let issue = self.issue_in_file(&file_name, &id, &[], message);
+					self.errors.push(issue);
+					continue;
+				}
+				origins.insert(id.clone(), file_name.clone());
+				entities.push((id, entity));
+			}
+		}
+		entities
+	}
 
-		let volumes_file = std::fs::File::open(path.as_ref().join("volumes.yaml"))?;
-		let mut raw_volumes: HashMap<String, Volume> = serde_yaml_ng::from_reader(volumes_file)?;
-		for (id, volume) in raw_volumes.iter_mut() {
-			volume.id = Some(id.to_string());
-			data.volumes.push(volume.clone());
+	/// Parse a single config file into a list of (id, entity) pairs.
+	///
+	/// Any keys which serde would otherwise silently discard (typos like `skip_backups` or `http-port`)
+	/// are reported, naming the file, entity id and offending key.  In strict mode they're errors;
+	/// in lenient mode they're logged as warnings instead.
+	fn load_file<T: DeserializeOwned>(&mut self, kind: &str, file_name: &str) -> Vec<(String, T)> {
+		let source = match self.source.read_file(file_name) {
+			Ok(source) => source,
+			Err(err) => {
+				self.errors.push(Issue { file: file_name.to_string(), line: None, column: None, entity: None, message: err.to_string() });
+				return vec![];
+			},
+		};
+		let source_map = SourceMap::parse(&source);
+		for duplicate in source_map.duplicates() {
+			let (id, path) = duplicate.path.split_first().expect("duplicate paths are never empty");
+			let message = match path.last() {
+				None => format!("duplicate id (also defined on line {})", duplicate.first.line),
+				Some(key) => format!("duplicate key `{}` (also defined on line {})", key, duplicate.first.line),
+			};
+			self.errors.push(Issue {
+				file: file_name.to_string(),
+				line: Some(duplicate.second.line),
+				column: Some(duplicate.second.column),
+				entity: Some(id.clone()),
+				message,
+			});
 		}
-		data.volumes.sort_by(|d1, d2| d1.id.cmp(&d2.id));
+		// serde_yaml_ng rejects duplicate keys, but they've already been reported more precisely,
+		// so check the rest of the file as though only the last of each had been given
+		let parsed = match source_map::parse_keeping_last(&source) {
+			Some(value) if !source_map.duplicates().is_empty() => serde_yaml_ng::from_value(value),
+			_ => serde_yaml_ng::from_str(&source),
+		};
+		let raw: HashMap<String, serde_yaml_ng::Value> = match parsed {
+			Ok(raw) => raw,
+			Err(err) => {
+				let location = err.location();
+				self.errors.push(Issue {
+					file: file_name.to_string(),
+					line: location.as_ref().map(|l| l.line()),
+					column: location.as_ref().map(|l| l.column()),
+					entity: None,
+					message: err.to_string(),
+				});
+				return vec![];
+			},
+		};
+		let mut entities = Vec::new();
+		let mut errors = Vec::new();
+		let mut warnings = Vec::new();
+		for (id, value) in raw {
+			let mut unknown_keys: Vec<Vec<String>> = Vec::new();
+			let mut record_unknown_key = |path: serde_ignored::Path| {
+				// serde_ignored marks optional values with a "?" segment, which doesn't correspond to anything in the YAML
+				unknown_keys.push(path.to_string().split('.').filter(|segment| *segment != "?").map(String::from).collect());
+			};
+			let deserializer = serde_ignored::Deserializer::new(value, &mut record_unknown_key);
+			match serde_path_to_error::deserialize::<_, T>(deserializer) {
+				Ok(entity) => entities.push((id.clone(), entity)),
+				Err(err) => {
+					let path: Vec<String> = err.path().iter().filter_map(|segment| match segment {
+						serde_path_to_error::Segment::Seq { index } => Some(index.to_string()),
+						serde_path_to_error::Segment::Map { key } => Some(key.clone()),
+						_ => None,
+					}).collect();
+					errors.push(entity_issue(file_name, &source_map, &id, &path, err.to_string()));
+					self.unparsed.entry(kind.to_string()).or_default().insert(id.clone());
+				},
+			}
+			for key in unknown_keys {
+				let issue = entity_issue(file_name, &source_map, &id, &key, format!("unknown key `{}`", key.join(".")));
+				match self.strictness {
+					Strictness::Strict => errors.push(issue),
+					Strictness::Lenient => warnings.push(issue),
+				}
+			}
+		}
+		errors.sort_by_key(|issue| (issue.line, issue.column));
+		warnings.sort_by_key(|issue| (issue.line, issue.column));
+		self.errors.extend(errors);
+		self.warnings.extend(warnings);
+		self.source_maps.insert(file_name.to_string(), source_map);
+		entities
+	}
 
-		let hosts_file = std::fs::File::open(path.as_ref().join("hosts.yaml"))?;
-		let mut raw_hosts: HashMap<String, Host> = serde_yaml_ng::from_reader(hosts_file)?;
-		for (id, host) in raw_hosts.iter_mut() {
-			host.id = Some(id.to_string());
-			data.hosts.push(host.clone());
+	/// Build an issue against an entity in the given file.
+	fn issue_in_file(&self, file_name: &str, id: &str, path: &[String], message: String) -> Issue {
+		match self.source_maps.get(file_name) {
+			Some(source_map) => entity_issue(file_name, source_map, id, path, message),
+			None => entity_issue(file_name, &SourceMap::default(), id, path, message),
 		}
-		data.hosts.sort_by(|d1, d2| d1.id.cmp(&d2.id));
+	}
+
+	/// Build an issue against an entity of the given kind which has already been loaded.
+	fn issue(&self, kind: &str, id: &str, path: &[String], message: String) -> Issue {
+		let file_name = self.origins.get(kind)
+			.and_then(|origins| origins.get(id))
+			.cloned()
+			.unwrap_or_else(|| format!("{}.yaml", kind));
+		self.issue_in_file(&file_name, id, path, message)
+	}
 
-		let components_file = std::fs::File::open(path.as_ref().join("components.yaml"))?;
-		let mut raw_components: HashMap<String, Component> = serde_yaml_ng::from_reader(components_file)?;
-		for (id, component) in raw_components.iter_mut() {
-			component.id = Some(id.to_string());
-			data.components.push(component.clone());
+	/// Check a single reference to a host.
+	/// Unknown and decommissioned hosts are errors; inactive hosts are only warned about, as they may come back.
+	fn check_host_reference(&mut self, hosts: &HashMap<&str, &HostStatus>, kind: &str, id: &str, path: &[String], host: &str) {
+		let field = &path[0];
+		match hosts.get(host) {
+			None if self.unparsed.get(Host::KIND).is_some_and(|ids| ids.contains(host)) => {},
+			None => {
+				let issue = self.issue(kind, id, path, format!("{} refers to unknown host {:?}", field, host));
+				self.errors.push(issue);
+			},
+			Some(HostStatus::Decommissioned) => {
+				let issue = self.issue(kind, id, path, format!("{} refers to decommissioned host {:?}", field, host));
+				self.errors.push(issue);
+			},
+			Some(HostStatus::Inactive) => {
+				let issue = self.issue(kind, id, path, format!("{} refers to inactive host {:?}", field, host));
+				self.warnings.push(issue);
+			},
+			Some(HostStatus::Active) => {},
 		}
-		data.components.sort_by(|d1, d2| d1.id.cmp(&d2.id));
+	}
 
-		let scripts_file = std::fs::File::open(path.as_ref().join("scripts.yaml"))?;
-		let mut raw_scripts: HashMap<String, Script> = serde_yaml_ng::from_reader(scripts_file)?;
-		for (id, script) in raw_scripts.iter_mut() {
-			script.id = Some(id.to_string());
-			data.scripts.push(script.clone());
+	/// Check that every reference to a host (from systems, volumes and other hosts) names a host which exists and is active.
+	fn check_host_references(&mut self, data: &Data) {
+		let hosts: HashMap<&str, &HostStatus> = data.hosts.iter()
+			.filter_map(|host| host.id.as_deref().map(|id| (id, &host.status)))
+			.collect();
+		for system in &data.systems {
+			let id = system.id.as_deref().unwrap_or_default();
+			for (index, host) in system.hosts.iter().enumerate() {
+				self.check_host_reference(&hosts, System::KIND, id, &["hosts".to_string(), index.to_string()], host);
+			}
+		}
+		for volume in &data.volumes {
+			let id = volume.id.as_deref().unwrap_or_default();
+			for (index, host) in volume.skip_backup_on_hosts.iter().enumerate() {
+				self.check_host_reference(&hosts, Volume::KIND, id, &["skip_backup_on_hosts".to_string(), index.to_string()], host);
+			}
+		}
+		for host in &data.hosts {
+			let id = host.id.as_deref().unwrap_or_default();
+			if let Some(gateway) = &host.ssh_gateway {
+				self.check_host_reference(&hosts, Host::KIND, id, &["ssh_gateway".to_string()], gateway);
+			}
 		}
-		data.scripts.sort_by(|d1, d2| d1.id.cmp(&d2.id));
+	}
+}
 
+impl Data {
+	pub fn from_dir<P: AsRef<Path>>(path: P) -> Result<Data, ValidationReport> {
+		Self::from_dir_with_strictness(path, Strictness::Strict)
+	}
+	/// Load config from a directory of YAML files.
+	/// Every problem found across all the files is gathered into the returned `ValidationReport`.
+	pub fn from_dir_with_strictness<P: AsRef<Path>>(path: P, strictness: Strictness) -> Result<Data, ValidationReport> {
+		Self::from_source(&path.as_ref(), strictness)
+	}
+	/// Load config from anywhere YAML files can be read from, such as a directory or a git commit.
+	pub fn from_source(source: &dyn ConfigSource, strictness: Strictness) -> Result<Data, ValidationReport> {
+		let mut loader = Loader {
+			source,
+			strictness,
+			errors: vec![],
+			warnings: vec![],
+			source_maps: HashMap::new(),
+			origins: HashMap::new(),
+			unparsed: HashMap::new(),
+		};
+		let mut data = Data {
+			systems: loader.load_collection(),
+			volumes: loader.load_collection(),
+			hosts: loader.load_collection(),
+			components: loader.load_collection(),
+			scripts: loader.load_collection(),
+			warnings: vec![],
+			version: String::new(),
+			loaded_at: SystemTime::now(),
+		};
+
+		loader.check_host_references(&data);
+
+		if !loader.errors.is_empty() {
+			return Err(ValidationReport { errors: loader.errors });
+		}
+		data.warnings = loader.warnings;
+		data.version = data.content_hash();
 		Ok(data)
 	}
+	/// SHA-256 of every entity, serialised as JSON.
+	/// serde_json sorts object keys, so formatting, comments and ordering in the YAML don't affect the hash.
+	fn content_hash(&self) -> String {
+		let content: serde_json::Map<String, Value> = self.collections().iter()
+			.map(|collection| (collection.kind().to_string(), collection.to_value()))
+			.collect();
+		let digest = Sha256::digest(Value::Object(content).to_string());
+		digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+	}
+	/// When the config was loaded.
+	pub fn loaded_at(&self) -> SystemTime {
+		self.loaded_at
+	}
+	/// Identifies the content of the loaded config.  Two loads of equivalent config have the same version.
+	pub fn version(&self) -> &str {
+		&self.version
+	}
+	/// Problems found while loading which didn't stop the config loading: those which only lenient mode lets through
+	/// (and which would have failed a strict load), and those which are only ever warnings, like references to inactive hosts.
+	pub fn warnings(&self) -> &[Issue] {
+		&self.warnings
+	}
+	/// Every kind of entity, in the order they're presented in.
+	pub fn collections(&self) -> [&dyn AnyCollection; 5] {
+		[&self.systems, &self.hosts, &self.volumes, &self.components, &self.scripts]
+	}
+	/// A short description of how much config was loaded, eg "3 systems; 2 hosts; ...".
+	pub fn summary(&self) -> String {
+		let counts: Vec<String> = self.collections().iter()
+			.map(|collection| format!("{} {}", collection.count(), collection.kind()))
+			.collect();
+		counts.join("; ")
+	}
 	pub fn system_count(&self) -> usize {
 		self.systems.len()
 	}
@@ -184,91 +622,68 @@ impl Data {
 	pub fn script_count(&self) -> usize {
 		self.scripts.len()
 	}
-	pub fn get_systems(&self) -> Vec<System> {
-		self.systems.clone()
+	pub fn get_systems(&self) -> &[Arc<System>] {
+		self.systems.as_slice()
 	}
-	pub fn get_systems_filtered<P>(&self, predicate: P) -> Vec<System>
+	pub fn get_systems_filtered<P>(&self, predicate: P) -> Vec<&System>
 	where
 		P: Fn(&System) -> bool,
 	{
-		self.get_systems()
-			.into_iter()
-			.filter(predicate)
+		self.systems.iter()
+			.filter(|system| predicate(system))
 			.collect()
 	}
-	pub fn get_volumes(&self) -> Vec<Volume> {
-		self.volumes.clone()
+	pub fn get_volumes(&self) -> &[Arc<Volume>] {
+		self.volumes.as_slice()
 	}
-	pub fn get_hosts(&self) -> Vec<Host> {
-		self.hosts.clone()
+	pub fn get_hosts(&self) -> &[Arc<Host>] {
+		self.hosts.as_slice()
 	}
-	pub fn get_hosts_filtered<P>(&self, predicate: P) -> Vec<Host>
+	pub fn get_hosts_filtered<P>(&self, predicate: P) -> Vec<&Host>
 	where
 		P: Fn(&Host) -> bool,
 	{
-		self.get_hosts()
-			.into_iter()
-			.filter(predicate)
+		self.hosts.iter()
+			.filter(|host| predicate(host))
 			.collect()
 	}
-	/// Look up a single host by id, returning its config as a JSON Value, or `None` if not found.
-	pub fn get_host(&self, id: &str) -> Option<Value> {
-		self.hosts.iter().find(|h| h.id.as_deref() == Some(id))
-			.map(|h| serde_json::to_value(h).unwrap())
+	/// Whether the host with the given id exists and is active.
+	pub fn is_host_active(&self, id: &str) -> bool {
+		self.hosts.get(id).is_some_and(|h| h.status == HostStatus::Active)
+	}
+	/// Look up a single host by id, or `None` if not found.
+	pub fn get_host(&self, id: &str) -> Option<&Arc<Host>> {
+		self.hosts.get(id)
 	}
-	pub fn get_components(&self) -> Vec<Component> {
-		self.components.clone()
+	pub fn get_components(&self) -> &[Arc<Component>] {
+		self.components.as_slice()
 	}
-	pub fn get_scripts(&self) -> Vec<Script> {
-		self.scripts.clone()
+	pub fn get_scripts(&self) -> &[Arc<Script>] {
+		self.scripts.as_slice()
 	}
 
-	/// Look up a repository by id across systems, components, and scripts.
+	/// Look up a repository by id across every kind of entity which is a code repository (systems, components, and scripts).
 	/// Returns the item serialised as a JSON Value with an additional `type` field,
 	/// or `None` if no match is found.
 	pub fn get_repository(&self, id: &str) -> Option<Value> {
-		if let Some(system) = self.systems.iter().find(|s| s.id.as_deref() == Some(id)) {
-			let mut value = serde_json::to_value(system).unwrap();
+		self.collections().into_iter().find_map(|collection| {
+			let repository_type = collection.repository_type()?;
+			let mut value = collection.get_value(id)?;
 			if let Value::Object(ref mut map) = value {
-				map.insert("type".to_string(), Value::String("system".to_string()));
+				map.insert("type".to_string(), Value::String(repository_type.to_string()));
 			}
-			return Some(value);
-		}
-		if let Some(component) = self.components.iter().find(|c| c.id.as_deref() == Some(id)) {
-			let mut value = serde_json::to_value(component).unwrap();
-			if let Value::Object(ref mut map) = value {
-				map.insert("type".to_string(), Value::String("component".to_string()));
-			}
-			return Some(value);
-		}
-		if let Some(script) = self.scripts.iter().find(|s| s.id.as_deref() == Some(id)) {
-			let mut value = serde_json::to_value(script).unwrap();
-			if let Value::Object(ref mut map) = value {
-				map.insert("type".to_string(), Value::String("script".to_string()));
-			}
-			return Some(value);
-		}
-		None
+			Some(value)
+		})
 	}
 
 	/// Returns all repository ids (across systems, components, and scripts) as a Vec of (id, type) pairs.
 	pub fn get_all_repository_ids(&self) -> Vec<(String, &'static str)> {
 		let mut ids = Vec::new();
-		for s in &self.systems {
-			if let Some(id) = &s.id {
-				ids.push((id.clone(), "system"));
-			}
-		}
-		for c in &self.components {
-			if let Some(id) = &c.id {
-				ids.push((id.clone(), "component"));
-			}
-		}
-		for s in &self.scripts {
-			if let Some(id) = &s.id {
-				ids.push((id.clone(), "script"));
+		for collection in self.collections() {
+			if let Some(repository_type) = collection.repository_type() {
+				ids.extend(collection.ids().into_iter().map(|id| (id, repository_type)));
 			}
 		}
 		ids
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use axum::{
+	extract::{Query, State},
+	response::{IntoResponse, Response},
+	http::{header, StatusCode},
+	http::header::HeaderMap,
+	Json,
+};
+use axum_yaml::Yaml;
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::conneg::negotiate;
+use crate::data::Data;
+use crate::history::load_revision;
+use crate::store::AppState;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeType {
+	Added,
+	Removed,
+	Modified,
+}
+
+/// A single field which differs between two versions of an entity.
+/// `before` is null for fields which have been set, and `after` is null for fields which have been unset.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FieldChange {
+	pub field: String,
+	pub before: Value,
+	pub after: Value,
+}
+
+/// An entity which differs between two versions of the config.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EntityChange {
+	/// The kind of entity, eg "systems".
+	pub kind: &'static str,
+	pub id: String,
+	pub change: ChangeType,
+	pub fields: Vec<FieldChange>,
+}
+
+/// Every field which differs between two serialised versions of an entity, in alphabetical order.
+/// A missing entity (or field) is treated as null.
+fn field_changes(before: &Value, after: &Value) -> Vec<FieldChange> {
+	let fields: BTreeSet<&String> = [before, after].iter()
+		.filter_map(|value| value.as_object())
+		.flat_map(|object| object.keys())
+		.filter(|field| *field != "id")
+		.collect();
+	fields.into_iter().filter_map(|field| {
+		let before = before.get(field).unwrap_or(&Value::Null);
+		let after = after.get(field).unwrap_or(&Value::Null);
+		(before != after).then(|| FieldChange { field: field.clone(), before: before.clone(), after: after.clone() })
+	}).collect()
+}
+
+/// Every entity which has been added, removed or modified between two versions of the config.
+/// Ordered by kind, then id.
+pub fn diff(from: &Data, to: &Data) -> Vec<EntityChange> {
+	let mut changes = Vec::new();
+	for (from_collection, to_collection) in from.collections().into_iter().zip(to.collections()) {
+		let ids: BTreeSet<String> = from_collection.ids().into_iter().chain(to_collection.ids()).collect();
+		for id in ids {
+			let before = from_collection.get_value(&id);
+			let after = to_collection.get_value(&id);
+			let change = match (&before, &after) {
+				(None, Some(_)) => ChangeType::Added,
+				(Some(_), None) => ChangeType::Removed,
+				_ => ChangeType::Modified,
+			};
+			let fields = field_changes(&before.unwrap_or_default(), &after.unwrap_or_default());
+			if change == ChangeType::Modified && fields.is_empty() {
+				continue;
+			}
+			changes.push(EntityChange { kind: to_collection.kind(), id, change, fields });
+		}
+	}
+	changes
+}
+
+/// One side of a diff: which revision was asked for, and the content hash of the config it resolved to.
+#[derive(Serialize)]
+struct DiffSide {
+	rev: String,
+	version: String,
+}
+
+#[derive(Serialize)]
+struct DiffResponse {
+	from: DiffSide,
+	to: DiffSide,
+	changes: Vec<EntityChange>,
+}
+
+impl fmt::Display for DiffResponse {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Changes from {} to {}:", self.from.rev, self.to.rev)?;
+		if self.changes.is_empty() {
+			writeln!(f, "  None")?;
+		}
+		for change in &self.changes {
+			let change_type = match change.change {
+				ChangeType::Added => "added",
+				ChangeType::Removed => "removed",
+				ChangeType::Modified => "modified",
+			};
+			writeln!(f, "{} {:?}: {}", change.kind, change.id, change_type)?;
+			for field in &change.fields {
+				match change.change {
+					ChangeType::Added => writeln!(f, "  + {}: {}", field.field, field.after)?,
+					ChangeType::Removed => writeln!(f, "  - {}: {}", field.field, field.before)?,
+					ChangeType::Modified => writeln!(f, "  ~ {}: {} -> {}", field.field, field.before, field.after)?,
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Deserialize)]
+pub struct DiffParams {
+	from: String,
+	/// Defaults to the config currently being served.
+	to: Option<String>,
+}
+
+/// Lists every entity which differs between two revisions of the config, field by field.
+pub async fn controller(
+	State(state): State<AppState>,
+	Query(params): Query<DiffParams>,
+	headers: HeaderMap,
+) -> Response {
+	let from = match load_revision(&state, &params.from).await {
+		Ok(data) => data,
+		Err(response) => return response,
+	};
+	let (to_rev, to) = match &params.to {
+		Some(rev) => match load_revision(&state, rev).await {
+			Ok(data) => (rev.clone(), data),
+			Err(response) => return response,
+		},
+		None => ("current".to_string(), state.store.current()),
+	};
+	let diff = DiffResponse {
+		changes: diff(&from, &to),
+		from: DiffSide { rev: params.from, version: from.version().to_string() },
+		to: DiffSide { rev: to_rev, version: to.version().to_string() },
+	};
+
+	let available_mimes = vec![
+		mime::APPLICATION_JSON,
+		Mime::from_str("application/x-yaml").unwrap(),
+		mime::TEXT_PLAIN,
+	];
+	let mime = match negotiate(&headers, available_mimes) {
+		Ok(mime) => mime,
+		Err(not_acceptable) => return not_acceptable.into_response(),
+	};
+	match mime.essence_str() {
+		"application/x-yaml" => Yaml(diff).into_response(),
+		"text/plain" => (
+			StatusCode::OK,
+			[(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+			diff.to_string(),
+		).into_response(),
+		_ => Json(diff).into_response(),
+	}
+}
@@ -0,0 +1,36 @@
+use axum::{
+	extract::Query,
+	response::Response,
+	http::header::HeaderMap,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::conneg::negotiate_response_single;
+use crate::data::{BackupStrategy, HostStatus, Protocol, RecreateEffort, ShellFlavour};
+use crate::registry::Record;
+
+#[derive(Serialize)]
+#[serde(transparent)]
+struct Enums(Value);
+
+impl Record for Enums {
+	fn fields() -> Vec<&'static str> {
+		vec!["backup_strategy", "protocol", "recreate_effort", "shell_flavour", "status"]
+	}
+}
+
+/// Lists the allowed values of each enumerated field, keyed by field name.
+/// Lets consumers such as lucos_backups check that they agree with configy about which values are valid.
+pub async fn all(
+	headers: HeaderMap,
+	params: Query<crate::conneg::Params>,
+) -> Response {
+	let enums = Enums(json!({
+		"backup_strategy": BackupStrategy::ALL.iter().map(BackupStrategy::as_str).collect::<Vec<_>>(),
+		"protocol": [Protocol::Tcp, Protocol::Udp],
+		"recreate_effort": RecreateEffort::ALL.iter().map(RecreateEffort::as_str).collect::<Vec<_>>(),
+		"shell_flavour": ShellFlavour::ALL.iter().map(ShellFlavour::as_str).collect::<Vec<_>>(),
+		"status": HostStatus::ALL.iter().map(HostStatus::as_str).collect::<Vec<_>>(),
+	}));
+	negotiate_response_single(&headers, params, Some(enums))
+}
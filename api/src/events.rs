@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use axum::{
+	extract::State,
+	http::header::HeaderMap,
+	response::sse::{Event, KeepAlive, Sse},
+};
+use serde::Serialize;
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt};
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use crate::data::Data;
+use crate::diff::{diff, ChangeType};
+use crate::store::ConfigStore;
+
+/// How many past changes are kept, for clients resuming with `Last-Event-ID`.
+const HISTORY_SIZE: usize = 64;
+
+/// An entity which was added, removed or modified by a change to the config.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangedEntity {
+	pub kind: &'static str,
+	pub id: String,
+	pub change: ChangeType,
+}
+
+/// Sent to `/events` subscribers whenever the served config changes.
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigChange {
+	pub version: String,
+	pub previous_version: String,
+	pub changes: Vec<ChangedEntity>,
+}
+
+impl ConfigChange {
+	pub fn between(previous: &Data, current: &Data) -> Self {
+		ConfigChange {
+			version: current.version().to_string(),
+			previous_version: previous.version().to_string(),
+			changes: diff(previous, current).into_iter()
+				.map(|change| ChangedEntity { kind: change.kind, id: change.id, change: change.change })
+				.collect(),
+		}
+	}
+}
+
+/// Fans each config change out to every `/events` subscriber, keeping recent changes for any which reconnect.
+pub struct ChangeFeed {
+	sender: broadcast::Sender<Arc<ConfigChange>>,
+	history: Mutex<VecDeque<Arc<ConfigChange>>>,
+}
+
+impl Default for ChangeFeed {
+	fn default() -> Self {
+		ChangeFeed {
+			sender: broadcast::channel(HISTORY_SIZE).0,
+			history: Mutex::new(VecDeque::with_capacity(HISTORY_SIZE)),
+		}
+	}
+}
+
+impl ChangeFeed {
+	/// Publish a change, calling `serve` to start serving the changed config first.
+	/// Both happen while holding the lock taken by `subscribe`, so a new subscriber either sees the old config
+	/// followed by the change, or the new config without it.
+	pub fn publish(&self, change: ConfigChange, serve: impl FnOnce()) {
+		let change = Arc::new(change);
+		let mut history = self.history.lock().unwrap();
+		serve();
+		if history.len() == HISTORY_SIZE {
+			history.pop_front();
+		}
+		history.push_back(change.clone());
+		// Only fails if there are no subscribers, which is fine
+		let _ = self.sender.send(change);
+	}
+
+	/// Start receiving changes, along with the version being served as of the first one, as given by `current_version`.
+	/// If `since` is a version in the recent history, also returns every change made after it,
+	/// otherwise returns `None` for the backlog, as the subscriber can't be brought up to date one change at a time.
+	fn subscribe(&self, since: Option<&str>, current_version: impl FnOnce() -> String) -> Subscription {
+		// Subscribe while holding the lock, so no change can be missed, or sent twice, between the backlog,
+		// the current version and the receiver
+		let history = self.history.lock().unwrap();
+		let receiver = self.sender.subscribe();
+		let current_version = current_version();
+		let backlog = since.and_then(|since| {
+			if history.back().is_some_and(|change| change.version == since) {
+				return Some(vec![]);
+			}
+			// A version can come round again if a change is reverted, in which case the latest occurrence is the one to resume from
+			let position = history.iter().rposition(|change| change.previous_version == since)?;
+			Some(history.iter().skip(position).cloned().collect())
+		});
+		Subscription { backlog, current_version, receiver }
+	}
+}
+
+struct Subscription {
+	backlog: Option<Vec<Arc<ConfigChange>>>,
+	current_version: String,
+	receiver: broadcast::Receiver<Arc<ConfigChange>>,
+}
+
+/// An event telling the client which version is current, without saying what changed.
+/// Sent when a client first connects (`version`), and when it's missed changes it can't be told about (`reset`).
+fn version_event(event_type: &str, version: &str) -> Event {
+	Event::default()
+		.event(event_type)
+		.id(version)
+		.json_data(serde_json::json!({ "version": version }))
+		.unwrap()
+}
+
+fn change_event(change: &ConfigChange) -> Event {
+	Event::default()
+		.event("change")
+		.id(&change.version)
+		.json_data(change)
+		.unwrap()
+}
+
+/// A Server-Sent Events stream with an event each time the served config changes.
+///
+/// Each event's id is the config's version, so a client reconnecting with a `Last-Event-ID` header
+/// is sent whatever changes it missed.  If they're no longer known, it's sent a `reset` event instead,
+/// and should re-fetch everything it needs.
+pub async fn stream(
+	State(store): State<Arc<ConfigStore>>,
+	headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+	let last_event_id = headers.get("Last-Event-ID").and_then(|h| h.to_str().ok());
+	let Subscription { backlog, current_version, receiver } = store.changes()
+		.subscribe(last_event_id, || store.current().version().to_string());
+
+	let initial: Vec<Event> = match (last_event_id, backlog) {
+		(Some(_), Some(backlog)) => backlog.iter().map(|change| change_event(change)).collect(),
+		(Some(last_event_id), None) if last_event_id == current_version => vec![],
+		(Some(_), None) => vec![version_event("reset", &current_version)],
+		(None, _) => vec![version_event("version", &current_version)],
+	};
+
+	let changes = BroadcastStream::new(receiver).map(move |change| match change {
+		Ok(change) => change_event(&change),
+		Err(BroadcastStreamRecvError::Lagged(_)) => version_event("reset", store.current().version()),
+	});
+	let events = tokio_stream::iter(initial).chain(changes).map(Ok);
+	Sse::new(events).keep_alive(KeepAlive::default())
+}
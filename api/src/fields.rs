@@ -0,0 +1,163 @@
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+/// Which fields to include in a response, as given by the `fields` param, eg `id,public_ports.port,-ipv6`.
+///
+/// Each field is a dotted path, which selects within objects, and within every item of a list.
+/// Fields prefixed with `-` are left out; any others are the only ones included.
+#[derive(Debug, Default, PartialEq)]
+pub struct FieldSelection {
+	includes: Vec<Vec<String>>,
+	excludes: Vec<Vec<String>>,
+}
+
+/// Fields were asked for which the entities don't have.  Responds with `400 Bad Request`, listing them.
+#[derive(Debug)]
+pub struct UnknownFields(pub Vec<String>);
+
+impl IntoResponse for UnknownFields {
+	fn into_response(self) -> Response {
+		(StatusCode::BAD_REQUEST, format!("Unknown field(s) in `fields`: {}", self.0.join(", "))).into_response()
+	}
+}
+
+fn parse_path(path: &str) -> Vec<String> {
+	path.split('.').map(String::from).collect()
+}
+
+fn join_path(path: &[String]) -> String {
+	path.join(".")
+}
+
+/// Keep only the parts of a value which are within one of the given paths.
+fn select(value: Value, paths: &[&[String]]) -> Value {
+	match value {
+		Value::Object(map) => Value::Object(map.into_iter()
+			.filter_map(|(key, value)| {
+				let within: Vec<&[String]> = paths.iter()
+					.filter(|path| path[0] == key)
+					.map(|path| &path[1..])
+					.collect();
+				if within.is_empty() {
+					None
+				} else if within.iter().any(|rest| rest.is_empty()) {
+					Some((key, value))
+				} else {
+					Some((key, select(value, &within)))
+				}
+			})
+			.collect()),
+		Value::Array(items) => Value::Array(items.into_iter().map(|item| select(item, paths)).collect()),
+		other => other,
+	}
+}
+
+fn remove(value: &mut Value, path: &[String]) {
+	match value {
+		Value::Object(map) => match path {
+			[key] => { map.remove(key); },
+			[key, rest @ ..] => if let Some(value) = map.get_mut(key) {
+				remove(value, rest);
+			},
+			[] => {},
+		},
+		Value::Array(items) => for item in items {
+			remove(item, path);
+		},
+		_ => {},
+	}
+}
+
+impl FieldSelection {
+	pub fn parse(fields: &str) -> Self {
+		let mut selection = FieldSelection::default();
+		for field in fields.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+			match field.strip_prefix('-') {
+				Some(excluded) => selection.excludes.push(parse_path(excluded)),
+				None => selection.includes.push(parse_path(field)),
+			}
+		}
+		selection
+	}
+
+	/// The fields which are to be included, as dotted paths, in the order they were given.  Empty if every field is.
+	pub fn includes(&self) -> Vec<String> {
+		self.includes.iter().map(|path| join_path(path)).collect()
+	}
+
+	/// Any of the given fields which aren't among the `known` fields (as given by `Record::fields`).
+	pub fn unknown_fields(&self, known: &[&str]) -> Vec<String> {
+		self.includes.iter().chain(&self.excludes)
+			.map(|path| join_path(path))
+			.filter(|field| !known.contains(&field.as_str()))
+			.collect()
+	}
+
+	/// Trim a single record down to the selected fields.
+	pub fn apply(&self, record: Value) -> Value {
+		let mut record = if self.includes.is_empty() {
+			record
+		} else {
+			let paths: Vec<&[String]> = self.includes.iter().map(Vec::as_slice).collect();
+			select(record, &paths)
+		};
+		for path in &self.excludes {
+			remove(&mut record, path);
+		}
+		record
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::json;
+
+	fn system() -> Value {
+		json!({
+			"id": "system1",
+			"hosts": ["host1"],
+			"http_port": null,
+			"public_ports": [
+				{ "port": 25, "protocol": "tcp" },
+				{ "port": 587, "protocol": "tcp" },
+			],
+		})
+	}
+
+	#[test]
+	fn nested_fields_are_selected_within_lists() {
+		let selection = FieldSelection::parse("id,public_ports.port");
+		assert_eq!(selection.apply(system()), json!({
+			"id": "system1",
+			"public_ports": [{ "port": 25 }, { "port": 587 }],
+		}));
+	}
+
+	#[test]
+	fn excluded_fields_are_removed() {
+		let selection = FieldSelection::parse("-hosts, -public_ports.protocol");
+		assert_eq!(selection.apply(system()), json!({
+			"id": "system1",
+			"http_port": null,
+			"public_ports": [{ "port": 25 }, { "port": 587 }],
+		}));
+
+		let selection = FieldSelection::parse("id,public_ports,-public_ports.port");
+		assert_eq!(selection.apply(system()), json!({
+			"id": "system1",
+			"public_ports": [{ "protocol": "tcp" }, { "protocol": "tcp" }],
+		}));
+	}
+
+	#[test]
+	fn unknown_fields_are_reported() {
+		let known = ["id", "hosts", "http_port", "public_ports", "public_ports.port", "public_ports.protocol"];
+		let selection = FieldSelection::parse("id,hostz,-ipv6,public_ports.port,public_ports.prot,id.length,http_port.anything");
+		assert_eq!(selection.unknown_fields(&known), ["hostz", "public_ports.prot", "id.length", "http_port.anything", "ipv6"]);
+		assert!(FieldSelection::parse("id,-public_ports.protocol").unknown_fields(&known).is_empty());
+	}
+}
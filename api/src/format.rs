@@ -0,0 +1,98 @@
+use axum::{
+	extract::{Query, Request},
+	http::{header, HeaderValue, StatusCode, Uri},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+/// The formats which can be chosen without an `Accept` header: the name to give as a `format` param (eg `?format=csv`),
+/// the file extension to use instead (eg `/systems.csv`), and the media type each stands for.
+const FORMATS: [(&str, &str, &str); 4] = [
+	("json", "json", "application/json"),
+	("yaml", "yaml", "application/x-yaml"),
+	("csv", "csv", "text/csv"),
+	("turtle", "ttl", "text/turtle"),
+];
+
+fn media_type(format: &str) -> Option<&'static str> {
+	FORMATS.iter().find(|(name, _, _)| *name == format).map(|(_, _, media_type)| *media_type)
+}
+
+fn extension_media_type(extension: &str) -> Option<&'static str> {
+	FORMATS.iter().find(|(_, known, _)| *known == extension).map(|(_, _, media_type)| *media_type)
+}
+
+#[derive(Deserialize)]
+struct FormatParams {
+	format: Option<String>,
+	/// Whether CSV has a header row, as with the `header` parameter of `text/csv` in an `Accept` header.
+	header: Option<String>,
+}
+
+/// Split a known format's extension off the end of a path, eg `/hosts/avalon.yaml` to `/hosts/avalon` and `yaml`.
+fn split_extension(path: &str) -> Option<(&str, &'static str)> {
+	let (stem, extension) = path.rsplit_once('.')?;
+	if stem.ends_with('/') || extension.contains('/') {
+		return None;
+	}
+	Some((stem, extension_media_type(extension)?))
+}
+
+fn bad_request(message: String) -> Response {
+	(StatusCode::BAD_REQUEST, message).into_response()
+}
+
+/// Lets clients choose a format with a `format` param or a file extension, rather than an `Accept` header,
+/// which is handier from a browser or `curl`, and in links.
+///
+/// Works by rewriting the request as if it had been made to the path without the extension, with an `Accept` header
+/// for the chosen format, so needs to run before routing.  If both are given, the `format` param wins.
+pub async fn select_format(request: Request, next: Next) -> Response {
+	let (mut parts, body) = request.into_parts();
+	let params = match Query::<FormatParams>::try_from_uri(&parts.uri) {
+		Ok(Query(params)) => params,
+		Err(rejection) => return rejection.into_response(),
+	};
+
+	let mut chosen = None;
+	if let Some((stem, media_type)) = split_extension(parts.uri.path()) {
+		let path_and_query = match parts.uri.query() {
+			Some(query) => format!("{}?{}", stem, query),
+			None => stem.to_string(),
+		};
+		parts.uri = path_and_query.parse::<Uri>().expect("part of a valid uri is still valid");
+		chosen = Some(media_type);
+	}
+	if let Some(format) = &params.format {
+		let Some(media_type) = media_type(format) else {
+			let known: Vec<&str> = FORMATS.iter().map(|(name, _, _)| *name).collect();
+			return bad_request(format!("Unknown format {:?}.  Known formats: {}", format, known.join(", ")));
+		};
+		chosen = Some(media_type);
+	}
+
+	if let Some(media_type) = chosen {
+		let accept = match (media_type, params.header.as_deref()) {
+			("text/csv", Some(header @ ("present" | "absent"))) => format!("{};header={}", media_type, header),
+			("text/csv", Some(header)) => return bad_request(format!("Unknown value for header {:?}.  Use \"present\" or \"absent\"", header)),
+			_ => media_type.to_string(),
+		};
+		parts.headers.insert(header::ACCEPT, HeaderValue::from_str(&accept).expect("media types are valid header values"));
+	}
+	next.run(Request::from_parts(parts, body)).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_known_extensions_of_the_last_segment_are_split() {
+		assert_eq!(split_extension("/hosts/avalon.yaml"), Some(("/hosts/avalon", "application/x-yaml")));
+		assert_eq!(split_extension("/systems.csv"), Some(("/systems", "text/csv")));
+		assert_eq!(split_extension("/systems/subdomain/l42.eu"), None);
+		assert_eq!(split_extension("/at/v1.csv/systems"), None);
+		assert_eq!(split_extension("/hosts/.csv"), None);
+	}
+}
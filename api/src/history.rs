@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use axum::{
+	extract::{Path, Request, State},
+	http::{HeaderValue, StatusCode, Uri},
+	response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+use crate::data::{Data, Strictness};
+use crate::source::{resolve_commit, GitSource};
+use crate::routing::CONFIG_VERSION_HEADER;
+use crate::store::{AppState, ConfigStore};
+
+/// How many past versions of the config to keep loaded at once.
+const CACHE_SIZE: usize = 16;
+
+/// The git repository config is committed to, for serving config as it was at past revisions.
+pub struct GitHistory {
+	repo: PathBuf,
+	/// Where the config directory lives, relative to the root of the repository.
+	config_path: String,
+	/// Config already loaded from past commits, with their shas, from least to most recently used.
+	cache: Mutex<VecDeque<(String, Arc<Data>)>>,
+}
+
+/// Why config couldn't be loaded at a revision.
+pub enum HistoryError {
+	UnknownRevision,
+	Invalid(crate::validation::ValidationReport),
+	Git(std::io::Error),
+}
+
+impl GitHistory {
+	pub fn new<P: Into<PathBuf>>(repo: P, config_path: &str) -> Self {
+		GitHistory {
+			repo: repo.into(),
+			config_path: config_path.to_string(),
+			cache: Mutex::new(VecDeque::new()),
+		}
+	}
+
+	/// Load the config as it was at the given revision (anything git understands, eg a sha, tag or `HEAD~3`).
+	///
+	/// Unknown keys are only warned about, as fields may since have been removed from the data model.
+	pub fn load(&self, rev: &str) -> Result<Arc<Data>, HistoryError> {
+		let commit = resolve_commit(&self.repo, rev)
+			.map_err(HistoryError::Git)?
+			.ok_or(HistoryError::UnknownRevision)?;
+		{
+			let mut cache = self.cache.lock().unwrap();
+			if let Some(index) = cache.iter().position(|(sha, _)| *sha == commit) {
+				let entry = cache.remove(index).expect("index was just found");
+				let data = entry.1.clone();
+				cache.push_back(entry);
+				return Ok(data);
+			}
+		}
+		let source = GitSource::new(&self.repo, &commit, &self.config_path).map_err(HistoryError::Git)?;
+		let data = Arc::new(Data::from_source(&source, Strictness::Lenient).map_err(HistoryError::Invalid)?);
+		let mut cache = self.cache.lock().unwrap();
+		// Another request may have loaded the same commit meanwhile
+		cache.retain(|(sha, _)| *sha != commit);
+		if cache.len() >= CACHE_SIZE {
+			cache.pop_front();
+		}
+		cache.push_back((commit, data.clone()));
+		Ok(data)
+	}
+}
+
+/// Load the config at the given revision for a request, without blocking the runtime while git runs.
+/// If it can't be loaded, returns the response explaining why.
+pub async fn load_revision(state: &AppState, rev: &str) -> Result<Arc<Data>, Response> {
+	let Some(history) = state.history.clone() else {
+		return Err((StatusCode::NOT_FOUND, "No git repository is configured, so past revisions aren't available").into_response());
+	};
+	let loaded = tokio::task::spawn_blocking({
+		let rev = rev.to_string();
+		move || history.load(&rev)
+	}).await.expect("loading config from git panicked");
+	loaded.map_err(|error| match error {
+		HistoryError::UnknownRevision => (StatusCode::NOT_FOUND, format!("Unknown revision {:?}", rev)).into_response(),
+		HistoryError::Invalid(report) => (StatusCode::UNPROCESSABLE_ENTITY, format!("Config at revision {:?} failed to load\n{}", rev, report)).into_response(),
+		HistoryError::Git(error) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to read revision {:?} from git: {}", rev, error)).into_response(),
+	})
+}
+
+/// Serves any of the data endpoints (eg `/at/{rev}/systems`) using the config as it was at the given revision.
+pub async fn at(
+	State(state): State<AppState>,
+	Path((rev, _rest)): Path<(String, String)>,
+	request: Request,
+) -> Response {
+	let data = match load_revision(&state, &rev).await {
+		Ok(data) => data,
+		Err(response) => return response,
+	};
+
+	// Hand the request on to the usual data endpoints, minus the `/at/{rev}` prefix
+	// (taken from the raw uri, rather than the `rest` path parameter, which has been percent-decoded)
+	let (mut parts, body) = request.into_parts();
+	let rest = parts.uri.path().splitn(4, '/').nth(3).unwrap_or_default();
+	let path_and_query = match parts.uri.query() {
+		Some(query) => format!("/{}?{}", rest, query),
+		None => format!("/{}", rest),
+	};
+	parts.uri = path_and_query.parse::<Uri>().expect("part of a valid uri is still valid");
+	// Otherwise the inner routes would see this route's path parameters as well as their own
+	parts.extensions.clear();
+	let request = Request::from_parts(parts, body);
+	let version = HeaderValue::from_str(data.version()).expect("versions are hex, so always valid header values");
+	let historic_state = AppState::new(ConfigStore::fixed(data));
+	let routes = crate::routing::data_routes(&historic_state).with_state(historic_state);
+	let mut response = crate::routing::with_format_selection(routes)
+		.oneshot(request)
+		.await
+		.into_response();
+	response.headers_mut().insert(CONFIG_VERSION_HEADER, version);
+	response
+}
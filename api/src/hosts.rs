@@ -1,31 +1,45 @@
 use std::sync::Arc;
 use axum::{
-	extract::{Query, Path, State},
+	extract::{Query, Path},
+	Extension,
 	response::Response,
 	http::header::HeaderMap,
 };
+use serde::Deserialize;
 use crate::conneg::{negotiate_response, negotiate_response_single};
+use crate::data::HostStatus;
+
+/// Query parameters for endpoints which leave out hosts that aren't active.
+#[derive(Deserialize)]
+pub struct LifecycleParams {
+	/// Include inactive and decommissioned hosts (defaults to false).
+	#[serde(default)]
+	pub include_inactive: bool,
+}
 
 pub async fn all(
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
+	Query(lifecycle): Query<LifecycleParams>,
 ) -> Response {
-	negotiate_response(&headers, params, data.get_hosts())
+	let hosts = data.get_hosts_filtered(|host| lifecycle.include_inactive || host.status == HostStatus::Active);
+	negotiate_response(&headers, params, hosts)
 }
 
 pub async fn http(
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
+	Query(lifecycle): Query<LifecycleParams>,
 ) -> Response {
-	let http_hosts = data.get_hosts_filtered(|host| host.serves_http);
+	let http_hosts = data.get_hosts_filtered(|host| host.serves_http && (lifecycle.include_inactive || host.status == HostStatus::Active));
 	negotiate_response(&headers, params, http_hosts)
 }
 
 pub async fn get(
 	Path(host_id): Path<String>,
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
@@ -6,6 +6,8 @@ use axum::{
 	Json,
 };
 use serde::Serialize;
+use crate::data::HostStatus;
+use crate::store::ConfigStore;
 
 #[derive(Serialize)]
 struct InfoCI {
@@ -17,13 +19,15 @@ struct InfoCI {
 struct Check {
 	ok: bool,
 	tech_detail: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	debug: Option<String>,
 }
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Metric {
-	tech_detail: &'static str,
-	value: u8,
+	tech_detail: String,
+	value: u64,
 }
 
 #[derive(Serialize)]
@@ -34,33 +38,102 @@ struct InfoResponse {
 	show_on_homepage: bool,
 	ci: InfoCI,
 	checks: HashMap<&'static str, Check>,
-	metrics: HashMap<&'static str, Metric>,
+	metrics: HashMap<String, Metric>,
+	/// The content hash of the config currently being served.
+	config_version: String,
 }
 
 pub async fn controller(
 	State(data): State<Arc<crate::data::Data>>,
+	State(store): State<Arc<ConfigStore>>,
 ) -> impl IntoResponse {
-	let mut metrics = HashMap::new();
-	metrics.insert("system-count", Metric {
-		tech_detail: "The total number of systems configured",
-		value: data.system_count() as u8,
-	});
-	metrics.insert("volume-count", Metric {
-		tech_detail: "The total number of volumes configured",
-		value: data.volume_count() as u8,
-	});
-	metrics.insert("host-count", Metric {
-		tech_detail: "The total number of hosts configured",
-		value: data.host_count() as u8,
+	let mut checks = HashMap::new();
+	let on_disk = tokio::task::spawn_blocking({
+		let store = store.clone();
+		move || store.disk_version()
+	}).await.expect("loading config from disk panicked");
+	if let Some(on_disk) = on_disk {
+		checks.insert("config-valid", Check {
+			ok: on_disk.is_ok(),
+			tech_detail: "Whether the config on disk loads without any errors",
+			debug: on_disk.as_ref().err().map(|report| report.to_string()),
+		});
+		let stale_reason = match &on_disk {
+			Ok(disk_version) if disk_version == data.version() => None,
+			Ok(disk_version) => Some(format!("Serving config version {}, but the config on disk is version {}", data.version(), disk_version)),
+			Err(_) => Some(format!("Serving config version {}, as the config on disk fails to load", data.version())),
+		};
+		checks.insert("config-current", Check {
+			ok: stale_reason.is_none(),
+			tech_detail: "Whether the config being served matches the config on disk",
+			debug: stale_reason,
+		});
+	}
+	let reload_error = store.reload_error();
+	checks.insert("config-reload", Check {
+		ok: reload_error.is_none(),
+		tech_detail: "Whether the config loaded successfully the last time it changed on disk",
+		debug: reload_error.map(|report| report.to_string()),
 	});
-	metrics.insert("component-count", Metric {
-		tech_detail: "The total number of components configured",
-		value: data.component_count() as u8,
-	});
-	metrics.insert("script-count", Metric {
-		tech_detail: "The total number of local scripts configured",
-		value: data.script_count() as u8,
+	let warnings = data.warnings();
+	// Warnings (eg a system on an inactive host) are expected from time to time, so only ever listed, and counted in a metric
+	checks.insert("config-warnings", Check {
+		ok: true,
+		tech_detail: "Lists any warnings from loading the config being served",
+		debug: (!warnings.is_empty()).then(|| {
+			let lines: Vec<String> = warnings.iter().map(|warning| format!("  {}", warning)).collect();
+			format!("{} warning(s) found in config:\n{}", warnings.len(), lines.join("\n"))
+		}),
 	});
+
+	let mut metrics = HashMap::new();
+	let mut add_metric = |name: String, tech_detail: String, value: usize| {
+		metrics.insert(name, Metric { tech_detail, value: value as u64 });
+	};
+	for collection in data.collections() {
+		add_metric(
+			format!("{}-count", collection.singular()),
+			format!("The total number of {} configured", collection.description()),
+			collection.count(),
+		);
+	}
+	let systems = data.get_systems();
+	// Keyed by host, as each host gets its own graph.  Hosts which aren't active are left out, as with the host-based endpoints
+	for host in data.get_hosts().iter().filter(|host| host.status == HostStatus::Active) {
+		let Some(host_id) = &host.id else { continue };
+		let systems_on_host: Vec<_> = systems.iter().filter(|system| system.hosts.contains(host_id)).collect();
+		add_metric(
+			format!("systems-on-{}", host_id),
+			format!("The number of systems configured to run on {}", host_id),
+			systems_on_host.len(),
+		);
+		add_metric(
+			format!("public-ports-on-{}", host_id),
+			format!("The number of public ports opened on {} by its systems", host_id),
+			systems_on_host.iter().map(|system| system.public_ports.len()).sum(),
+		);
+	}
+	add_metric(
+		"skip-backup-volume-count".to_string(),
+		"The number of volumes which are never backed up".to_string(),
+		data.get_volumes().iter().filter(|volume| volume.skip_backup).count(),
+	);
+	add_metric(
+		"unsupervised-system-count".to_string(),
+		"The number of systems whose code may be changed by agents without human review".to_string(),
+		systems.iter().filter(|system| system.unsupervised_agent_code).count(),
+	);
+	add_metric(
+		"config-warning-count".to_string(),
+		"The number of warnings from loading the config being served".to_string(),
+		warnings.len(),
+	);
+	let config_age = data.loaded_at().elapsed().unwrap_or_default();
+	add_metric(
+		"config-age-seconds".to_string(),
+		"The number of seconds since the config being served was loaded".to_string(),
+		config_age.as_secs() as usize,
+	);
 	Json(InfoResponse {
 		system: "lucos_configy",
 		title: "LucOS Configy",
@@ -69,7 +142,8 @@ pub async fn controller(
 		},
 		network_only: true,
 		show_on_homepage: false,
-		checks: HashMap::new(),
-		metrics: metrics,
+		checks,
+		metrics,
+		config_version: data.version().to_string(),
 	})
 }
\ No newline at end of file
@@ -1,11 +1,24 @@
+pub mod admin;
 pub mod data;
+pub mod diff;
+pub mod fields;
 pub mod info;
 pub mod systems;
 pub mod volumes;
+pub mod history;
 pub mod hosts;
 pub mod components;
 pub mod scripts;
 pub mod repositories;
+pub mod conditional;
 pub mod conneg;
+pub mod format;
 pub mod routing;
 pub mod all;
+pub mod enums;
+pub mod events;
+pub mod registry;
+pub mod source;
+pub mod source_map;
+pub mod store;
+pub mod validation;
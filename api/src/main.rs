@@ -1,9 +1,16 @@
-use lucos_configy_api::routing::app;
+use lucos_configy_api::routing::router;
 use lucos_configy_api::data::Data;
+use lucos_configy_api::history::GitHistory;
+use lucos_configy_api::store::{AppState, ConfigStore};
 use std::{env, net::SocketAddr};
 use tokio::signal;
 use std::sync::Arc;
 
+// Where the config is served from, unless overridden by the `CONFIG_DIR` environment variable
+const DEFAULT_CONFIG_DIR: &str = "config";
+// Where the config lives within the repository, for reading past revisions of it
+const REPO_CONFIG_PATH: &str = "config";
+
 // Wait for SIGINT or SIGTERM
 async fn shutdown_signal() {
 	let ctrl_c = async {
@@ -31,25 +38,61 @@ async fn shutdown_signal() {
 	println!("Signal received, starting graceful shutdown...");
 }
 
+// Reload the config on SIGHUP, in addition to whenever it changes on disk
+#[cfg(unix)]
+async fn reload_on_hangup(store: Arc<ConfigStore>) {
+	let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())
+		.expect("failed to install SIGHUP handler");
+	while hangup.recv().await.is_some() {
+		let outcome = tokio::task::spawn_blocking({
+			let store = store.clone();
+			move || store.reload_outcome()
+		}).await.expect("reloading config panicked");
+		println!("{} (requested via SIGHUP)", outcome);
+	}
+}
+
 #[tokio::main]
 async fn main() {
-	let arc_data;
-	match Data::from_dir("config") {
+	let config_dir = env::var("CONFIG_DIR").ok()
+		.filter(|dir| !dir.is_empty())
+		.unwrap_or_else(|| DEFAULT_CONFIG_DIR.to_string());
+	let store = match Data::from_dir(&config_dir) {
 		Ok(data) => {
-			println!("Loaded {} systems; {} volumes; {} hosts", data.system_count(), data.volume_count(), data.host_count());
-			arc_data = Arc::new(data);
+			println!("Loaded {} from {}", data.summary(), config_dir);
+			for warning in data.warnings() {
+				eprintln!("WARNING: {}", warning);
+			}
+			ConfigStore::new(&config_dir, data)
 		}
-		Err(err) => {
-			panic!("Failed to load config, ({:?})", err);
+		Err(report) => {
+			eprintln!("Failed to load config\n{}", report);
+			std::process::exit(1);
 		}
-	}
+	};
+	let history = env::var("CONFIG_GIT_REPO").ok()
+		.filter(|repo| !repo.is_empty())
+		.map(|repo| GitHistory::new(repo, REPO_CONFIG_PATH));
+	let state = AppState::new(store)
+		.with_admin_token(env::var("ADMIN_TOKEN").ok())
+		.with_history(history);
+	#[cfg(unix)]
+	tokio::spawn(reload_on_hangup(state.store.clone()));
+	// Keep hold of the watcher, as changes stop being watched once it's dropped
+	let _watcher = match state.store.watch() {
+		Ok(watcher) => Some(watcher),
+		Err(error) => {
+			eprintln!("Can't watch config directory for changes, so it won't be reloaded: {}", error);
+			None
+		}
+	};
 
 	let port: u16 = env::var("PORT")
 		.ok()
 		.and_then(|p| p.parse().ok())
 		.unwrap_or(3000);
 
-	let app = app(arc_data);
+	let app = router(state);
 
 	let addr = SocketAddr::from(([0, 0, 0, 0], port));
 	println!("Listening on {}", addr);
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::all::ToTurtle;
+use crate::data::{System, Volume, Host, Component, Script, ShellFlavour};
+
+/// Something the endpoints respond with a list or one of, such as an entity.
+///
+/// Describes the fields it has, so that those asked for with the `fields` param can be checked,
+/// whether or not there are any records to check them against.
+pub trait Record {
+	/// Every field, as a dotted path, including those within nested objects and lists, eg `public_ports.port`.
+	fn fields() -> Vec<&'static str>;
+	/// The value consumers are documented to assume for any of the record's own top-level fields which aren't set.
+	/// Filled in by `?defaults=explicit`, so consumers don't need to hardcode them.
+	fn defaults() -> Vec<(&'static str, Value)> {
+		Vec::new()
+	}
+}
+
+impl<T: Record + ?Sized> Record for &T {
+	fn fields() -> Vec<&'static str> {
+		T::fields()
+	}
+	fn defaults() -> Vec<(&'static str, Value)> {
+		T::defaults()
+	}
+}
+
+impl<T: Record + ?Sized> Record for Arc<T> {
+	fn fields() -> Vec<&'static str> {
+		T::fields()
+	}
+	fn defaults() -> Vec<(&'static str, Value)> {
+		T::defaults()
+	}
+}
+
+/// A kind of entity which is loaded from the config, eg systems or hosts.
+///
+/// Declaring a kind here is all that's needed for it to be loaded, counted, looked up,
+/// reported in `/_info` metrics and exported as turtle.
+pub trait Entity: Record + Serialize + DeserializeOwned + Clone + ToTurtle + Send + Sync + 'static {
+	/// The plural name of the kind, eg "systems".
+	/// Entities are loaded from `{KIND}.yaml` and/or `{KIND}/*.yaml` in the config directory.
+	const KIND: &'static str;
+	/// The singular name of the kind, eg "system".  Used for metric names.
+	const SINGULAR: &'static str;
+	/// How to describe the kind to humans, eg in metric descriptions.
+	const DESCRIPTION: &'static str;
+	/// The name of the kind's class in the turtle ontology, eg "System".
+	const TURTLE_CLASS: &'static str;
+	/// The `type` given by `/repositories/{id}` for entities of this kind, or `None` if they aren't code repositories.
+	const REPOSITORY_TYPE: Option<&'static str> = None;
+
+	fn id(&self) -> Option<&str>;
+	/// Ids are given as keys in the YAML, rather than as an attribute, so get set after deserialisation.
+	fn set_id(&mut self, id: String);
+	/// Called once the entity's id has been set, to fill in any derived fields.
+	fn after_load(&mut self) {}
+}
+
+/// All the loaded entities of a single kind, sorted by id.
+///
+/// Entities are held behind `Arc`s, so handing them out (or sharing them with a newer copy of the config)
+/// never deep-copies them, and are indexed by id so lookups don't scan the whole collection.
+pub struct Collection<T: Entity> {
+	items: Vec<Arc<T>>,
+	index: HashMap<String, usize>,
+}
+
+impl<T: Entity> Collection<T> {
+	pub fn new(mut items: Vec<T>) -> Self {
+		items.sort_by(|a, b| a.id().cmp(&b.id()));
+		let index = items.iter().enumerate()
+			.filter_map(|(position, item)| Some((item.id()?.to_string(), position)))
+			.collect();
+		Collection {
+			items: items.into_iter().map(Arc::new).collect(),
+			index,
+		}
+	}
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+	pub fn iter(&self) -> impl Iterator<Item = &T> {
+		self.items.iter().map(|item| item.as_ref())
+	}
+	/// Every entity, in id order.  Serialises as a list.
+	pub fn as_slice(&self) -> &[Arc<T>] {
+		&self.items
+	}
+	pub fn get(&self, id: &str) -> Option<&Arc<T>> {
+		self.index.get(id).map(|&position| &self.items[position])
+	}
+}
+
+impl<'a, T: Entity> IntoIterator for &'a Collection<T> {
+	type Item = &'a T;
+	type IntoIter = std::iter::Map<std::slice::Iter<'a, Arc<T>>, fn(&'a Arc<T>) -> &'a T>;
+	fn into_iter(self) -> Self::IntoIter {
+		self.items.iter().map(|item| item.as_ref())
+	}
+}
+
+impl<T: Entity> Default for Collection<T> {
+	fn default() -> Self {
+		Collection { items: vec![], index: HashMap::new() }
+	}
+}
+
+/// The parts of a `Collection` which don't depend on its entity type,
+/// so that every kind of entity can be handled in one loop.
+pub trait AnyCollection {
+	fn kind(&self) -> &'static str;
+	fn singular(&self) -> &'static str;
+	fn description(&self) -> &'static str;
+	fn turtle_class(&self) -> &'static str;
+	fn repository_type(&self) -> Option<&'static str>;
+	fn count(&self) -> usize;
+	fn ids(&self) -> Vec<String>;
+	/// Look up a single entity by id, serialised as a JSON Value.
+	fn get_value(&self, id: &str) -> Option<Value>;
+	/// All the entities, serialised as a JSON array.
+	fn to_value(&self) -> Value;
+	/// The documented defaults for the entities' fields, as given by `Record::defaults`.
+	fn defaults(&self) -> Vec<(&'static str, Value)>;
+	fn to_turtle(&self, base: &str) -> String;
+}
+
+impl<T: Entity> AnyCollection for Collection<T> {
+	fn kind(&self) -> &'static str {
+		T::KIND
+	}
+	fn singular(&self) -> &'static str {
+		T::SINGULAR
+	}
+	fn description(&self) -> &'static str {
+		T::DESCRIPTION
+	}
+	fn turtle_class(&self) -> &'static str {
+		T::TURTLE_CLASS
+	}
+	fn repository_type(&self) -> Option<&'static str> {
+		T::REPOSITORY_TYPE
+	}
+	fn count(&self) -> usize {
+		self.len()
+	}
+	fn ids(&self) -> Vec<String> {
+		self.iter().filter_map(|item| item.id().map(String::from)).collect()
+	}
+	fn get_value(&self, id: &str) -> Option<Value> {
+		self.get(id).map(|item| serde_json::to_value(item).unwrap())
+	}
+	fn to_value(&self) -> Value {
+		serde_json::to_value(&self.items).unwrap()
+	}
+	fn defaults(&self) -> Vec<(&'static str, Value)> {
+		T::defaults()
+	}
+	fn to_turtle(&self, base: &str) -> String {
+		self.iter().map(|item| item.to_turtle(base)).collect()
+	}
+}
+
+impl Record for System {
+	fn fields() -> Vec<&'static str> {
+		vec![
+			"id",
+			"domain",
+			"http_port",
+			"hosts",
+			"unsupervisedAgentCode",
+			"public_ports",
+			"public_ports.port",
+			"public_ports.protocol",
+			"public_ports.purpose",
+		]
+	}
+}
+
+impl Entity for System {
+	const KIND: &'static str = "systems";
+	const SINGULAR: &'static str = "system";
+	const DESCRIPTION: &'static str = "systems";
+	const TURTLE_CLASS: &'static str = "System";
+	const REPOSITORY_TYPE: Option<&'static str> = Some("system");
+	fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+	fn set_id(&mut self, id: String) {
+		self.id = Some(id);
+	}
+}
+
+impl Record for Host {
+	fn fields() -> Vec<&'static str> {
+		vec![
+			"id",
+			"status",
+			"domain",
+			"ipv4",
+			"ipv6",
+			"ipv4_nat",
+			"ipv4_private",
+			"ipv6_private",
+			"lan_only",
+			"serves_http",
+			"ssh_gateway",
+			"backup_root",
+			"is_storage_only",
+			"shell_flavour",
+			"can_reach_external_services",
+			"firewall_enforce",
+		]
+	}
+	fn defaults() -> Vec<(&'static str, Value)> {
+		vec![
+			("backup_root", "/srv/backups/".into()),
+			("shell_flavour", ShellFlavour::Gnu.as_str().into()),
+		]
+	}
+}
+
+impl Entity for Host {
+	const KIND: &'static str = "hosts";
+	const SINGULAR: &'static str = "host";
+	const DESCRIPTION: &'static str = "hosts";
+	const TURTLE_CLASS: &'static str = "Host";
+	fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+	fn set_id(&mut self, id: String) {
+		self.id = Some(id);
+	}
+	fn after_load(&mut self) {
+		self.set_derived_fields();
+	}
+}
+
+impl Record for Volume {
+	fn fields() -> Vec<&'static str> {
+		vec!["id", "description", "recreate_effort", "skip_backup", "skip_backup_on_hosts", "backup_strategy"]
+	}
+}
+
+impl Entity for Volume {
+	const KIND: &'static str = "volumes";
+	const SINGULAR: &'static str = "volume";
+	const DESCRIPTION: &'static str = "volumes";
+	const TURTLE_CLASS: &'static str = "Volume";
+	fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+	fn set_id(&mut self, id: String) {
+		self.id = Some(id);
+	}
+}
+
+impl Record for Component {
+	fn fields() -> Vec<&'static str> {
+		vec!["id", "unsupervisedAgentCode"]
+	}
+}
+
+impl Entity for Component {
+	const KIND: &'static str = "components";
+	const SINGULAR: &'static str = "component";
+	const DESCRIPTION: &'static str = "components";
+	const TURTLE_CLASS: &'static str = "Component";
+	const REPOSITORY_TYPE: Option<&'static str> = Some("component");
+	fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+	fn set_id(&mut self, id: String) {
+		self.id = Some(id);
+	}
+}
+
+impl Record for Script {
+	fn fields() -> Vec<&'static str> {
+		vec!["id", "unsupervisedAgentCode"]
+	}
+}
+
+impl Entity for Script {
+	const KIND: &'static str = "scripts";
+	const SINGULAR: &'static str = "script";
+	const DESCRIPTION: &'static str = "local scripts";
+	const TURTLE_CLASS: &'static str = "Script";
+	const REPOSITORY_TYPE: Option<&'static str> = Some("script");
+	fn id(&self) -> Option<&str> {
+		self.id.as_deref()
+	}
+	fn set_id(&mut self, id: String) {
+		self.id = Some(id);
+	}
+}
@@ -1,17 +1,39 @@
 use std::sync::Arc;
 use axum::{
-	extract::{Query, Path, State},
+	extract::{Query, Path},
+	Extension,
 	response::Response,
 	http::header::HeaderMap,
 };
+use serde::Serialize;
+use serde_json::Value;
 use crate::conneg::negotiate_response_single;
+use crate::data::{Component, Script, System};
+use crate::registry::Record;
+
+/// A system, component or script (whichever kinds of entity are code repositories), with its `type` added.
+#[derive(Serialize)]
+#[serde(transparent)]
+struct Repository(Value);
+
+impl Record for Repository {
+	fn fields() -> Vec<&'static str> {
+		let mut fields = vec!["type"];
+		for field in [System::fields(), Component::fields(), Script::fields()].concat() {
+			if !fields.contains(&field) {
+				fields.push(field);
+			}
+		}
+		fields
+	}
+}
 
 pub async fn get(
 	Path(id): Path<String>,
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
-	let item = data.get_repository(&id);
+	let item = data.get_repository(&id).map(Repository);
 	negotiate_response_single(&headers, params, item)
 }
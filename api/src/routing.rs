@@ -1,31 +1,82 @@
 use axum::{
-	response::Redirect,
-	routing::get,
+	extract::{Request, State},
+	http::HeaderValue,
+	middleware::{self, Next},
+	response::{Redirect, Response},
+	routing::{get, post},
 	Router,
 };
 use std::sync::Arc;
+use tower::Layer;
+use crate::store::{AppState, ConfigStore};
 
+pub const CONFIG_VERSION_HEADER: &str = "X-Config-Version";
+
+/// An app which serves the given config for as long as it runs.
 pub fn app(arc_data: Arc<crate::data::Data>) -> Router {
-	Router::new()
-		.route("/all", get(crate::all::all))
+	router(AppState::new(ConfigStore::fixed(arc_data)))
+}
+
+/// An app which serves whichever config is current in the state's store.
+pub fn router(state: AppState) -> Router {
+	// Every endpoint which negotiates a format, so can have it chosen by a `format` param or file extension
+	let formatted = data_routes(&state)
 		.route("/", get(Redirect::temporary("/systems")))
+		.route("/systems{*_subpath}", get(Redirect::temporary("/systems")))
+		.route("/volumes{*_subpath}", get(Redirect::temporary("/volumes")))
+		.route("/hosts{*_subpath}", get(Redirect::temporary("/hosts")))
+		.route("/components{*_subpath}", get(Redirect::temporary("/components")))
+		.route("/scripts{*_subpath}", get(Redirect::temporary("/scripts")))
+		.route("/enums", get(crate::enums::all))
+		.with_state(state.clone());
+	Router::new()
 		.route("/_info", get(crate::info::controller))
+		.route("/_admin/reload", post(crate::admin::reload))
+		.route("/at/{rev}/{*rest}", get(crate::history::at))
+		.route("/diff", get(crate::diff::controller))
+		.route("/events", get(crate::events::stream))
+		.fallback_service(with_format_selection(formatted))
+		.layer(middleware::from_fn_with_state(state.clone(), config_version_header))
+		.with_state(state)
+}
+
+/// Lets the format the given routes respond in be chosen by a `format` param or file extension, as well as `Accept`.
+///
+/// Middleware added with `Router::layer` only runs once a route has been matched,
+/// whereas choosing a format by file extension changes which route matches, so it wraps the routes as a whole.
+pub fn with_format_selection(routes: Router) -> Router {
+	Router::new().fallback_service(middleware::from_fn(crate::format::select_format).layer(routes))
+}
+
+/// The endpoints which serve config, and so can also be served for past revisions under `/at/{rev}`.
+///
+/// Their responses depend only on the config and the request, so they all support conditional requests.
+/// They get the config from an `Extension<Arc<Data>>`, which is the snapshot their validators are generated from.
+pub fn data_routes(state: &AppState) -> Router<AppState> {
+	Router::new()
+		.route("/all", get(crate::all::all))
 		.route("/systems", get(crate::systems::all))
 		.route("/systems/subdomain/{root_domain}", get(crate::systems::subdomain))
 		.route("/systems/http", get(crate::systems::http))
 		.route("/systems/host/{host}", get(crate::systems::host))
 		.route("/systems/host/{host}/public-ports", get(crate::systems::host_public_ports))
-		.route("/systems{*_subpath}", get(Redirect::temporary("/systems")))
 		.route("/volumes", get(crate::volumes::all))
-		.route("/volumes{*_subpath}", get(Redirect::temporary("/volumes")))
 		.route("/hosts", get(crate::hosts::all))
 		.route("/hosts/http", get(crate::hosts::http))
 		.route("/hosts/{host}", get(crate::hosts::get))
-		.route("/hosts{*_subpath}", get(Redirect::temporary("/hosts")))
 		.route("/components", get(crate::components::all))
-		.route("/components{*_subpath}", get(Redirect::temporary("/components")))
 		.route("/scripts", get(crate::scripts::all))
-		.route("/scripts{*_subpath}", get(Redirect::temporary("/scripts")))
 		.route("/repositories/{id}", get(crate::repositories::get))
-		.with_state(arc_data)
+		.route_layer(middleware::from_fn_with_state(state.clone(), crate::conditional::conditional_get))
+}
+
+/// Tells clients which version of the config they've been served.
+/// The data endpoints, and those which serve some other version (eg `/at/{rev}`), set the header themselves.
+async fn config_version_header(State(data): State<Arc<crate::data::Data>>, request: Request, next: Next) -> Response {
+	let mut response = next.run(request).await;
+	if !response.headers().contains_key(CONFIG_VERSION_HEADER) {
+		let version = HeaderValue::from_str(data.version()).expect("versions are hex, so always valid header values");
+		response.headers_mut().insert(CONFIG_VERSION_HEADER, version);
+	}
+	response
 }
@@ -1,13 +1,14 @@
 use std::sync::Arc;
 use axum::{
-	extract::{Query, State},
+	extract::Query,
+	Extension,
 	response::Response,
 	http::header::HeaderMap,
 };
 use crate::conneg::negotiate_response;
 
 pub async fn all(
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
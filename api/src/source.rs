@@ -0,0 +1,102 @@
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Somewhere config files can be read from, addressed by their path relative to the config directory.
+pub trait ConfigSource {
+	fn is_file(&self, file_name: &str) -> bool;
+	/// The names of the files directly within the given directory, in no particular order.
+	/// Empty if there's no such directory.
+	fn list_files(&self, dir_name: &str) -> Vec<String>;
+	fn read_file(&self, file_name: &str) -> io::Result<String>;
+}
+
+/// A config directory on disk.
+impl<P: AsRef<Path> + ?Sized> ConfigSource for P {
+	fn is_file(&self, file_name: &str) -> bool {
+		Path::is_file(&self.as_ref().join(file_name))
+	}
+	fn list_files(&self, dir_name: &str) -> Vec<String> {
+		match std::fs::read_dir(self.as_ref().join(dir_name)) {
+			Ok(entries) => entries
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.file_name().to_string_lossy().into_owned())
+				.collect(),
+			Err(_) => vec![],
+		}
+	}
+	fn read_file(&self, file_name: &str) -> io::Result<String> {
+		std::fs::read_to_string(self.as_ref().join(file_name))
+	}
+}
+
+/// A config directory as it was at a single commit in a git repository.
+///
+/// Shells out to the `git` CLI, so `git` needs to be installed wherever this is used.
+pub struct GitSource {
+	repo: PathBuf,
+	commit: String,
+	/// Where the config directory lives, relative to the root of the repository.
+	config_path: String,
+	/// Every file in the config directory at the commit, relative to the config directory.
+	files: HashSet<String>,
+}
+
+/// Run a git command in the given repository, returning its stdout.
+fn git(repo: &Path, args: &[&str]) -> io::Result<String> {
+	let output = Command::new("git").arg("-C").arg(repo).args(args).output()?;
+	if !output.status.success() {
+		return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+	}
+	String::from_utf8(output.stdout).map_err(io::Error::other)
+}
+
+/// Resolve any revision git understands (a sha, branch, tag, `HEAD~3` etc) to the full sha of a commit.
+/// Returns `Ok(None)` if the revision doesn't exist.
+pub fn resolve_commit(repo: &Path, rev: &str) -> io::Result<Option<String>> {
+	// Don't let a revision be mistaken for a command line option
+	if rev.starts_with('-') {
+		return Ok(None);
+	}
+	match git(repo, &["rev-parse", "--verify", "--quiet", "--end-of-options", &format!("{}^{{commit}}", rev)]) {
+		Ok(sha) => Ok(Some(sha.trim().to_string())),
+		Err(_) if git(repo, &["rev-parse", "--git-dir"]).is_ok() => Ok(None),
+		Err(error) => Err(error),
+	}
+}
+
+impl GitSource {
+	/// The config directory at the given commit, which should be a full sha as returned by `resolve_commit`.
+	pub fn new<P: AsRef<Path>>(repo: P, commit: &str, config_path: &str) -> io::Result<Self> {
+		let repo = repo.as_ref().to_path_buf();
+		let config_path = config_path.trim_matches('/').to_string();
+		let prefix = format!("{}/", config_path);
+		let files = git(&repo, &["ls-tree", "-r", "--name-only", commit, "--", &prefix])?
+			.lines()
+			.filter_map(|path| path.strip_prefix(&prefix))
+			.map(String::from)
+			.collect();
+		Ok(GitSource { repo, commit: commit.to_string(), config_path, files })
+	}
+}
+
+impl ConfigSource for GitSource {
+	fn is_file(&self, file_name: &str) -> bool {
+		self.files.contains(file_name)
+	}
+	fn list_files(&self, dir_name: &str) -> Vec<String> {
+		let prefix = format!("{}/", dir_name);
+		self.files.iter()
+			.filter_map(|path| path.strip_prefix(&prefix))
+			.filter(|name| !name.contains('/'))
+			.map(String::from)
+			.collect()
+	}
+	fn read_file(&self, file_name: &str) -> io::Result<String> {
+		if !self.is_file(file_name) {
+			return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't exist at commit {}", file_name, self.commit)));
+		}
+		git(&self.repo, &["cat-file", "blob", &format!("{}:{}/{}", self.commit, self.config_path, file_name)])
+	}
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+use serde_yaml_ng::{Mapping, Value};
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::{Marker, TScalarStyle};
+
+/// A position in a YAML file.  Both line and column are 1-indexed, matching serde_yaml_ng's errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+	pub line: usize,
+	pub column: usize,
+}
+
+impl From<Marker> for Location {
+	fn from(marker: Marker) -> Self {
+		Location {
+			line: marker.line(),
+			column: marker.col() + 1,
+		}
+	}
+}
+
+/// Records where each key (and each sequence item) appears in a YAML file,
+/// so problems found after deserialisation can be reported against a line in the source.
+///
+/// Paths are the chain of mapping keys and sequence indexes leading to a node,
+/// eg `["lucos_mail", "public_ports", "0", "port"]`.
+#[derive(Default, Debug)]
+pub struct SourceMap {
+	locations: HashMap<Vec<String>, Location>,
+	duplicates: Vec<Duplicate>,
+}
+
+/// A key which appears more than once in the same mapping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+	/// The path to the duplicated key, including the key itself.
+	pub path: Vec<String>,
+	pub first: Location,
+	pub second: Location,
+}
+
+impl SourceMap {
+	/// Index a YAML document.  If the YAML can't be parsed, whatever was indexed before the error is kept;
+	/// the parse error itself gets reported by serde_yaml_ng.
+	pub fn parse(source: &str) -> SourceMap {
+		let mut builder = Builder::default();
+		let mut parser = Parser::new_from_str(source);
+		let _ = parser.load(&mut builder, false);
+		SourceMap {
+			locations: builder.locations,
+			duplicates: builder.duplicates,
+		}
+	}
+
+	/// Every key which appears more than once in the same mapping, in the order they appear.
+	pub fn duplicates(&self) -> &[Duplicate] {
+		&self.duplicates
+	}
+
+	/// The location of the node at the given path, or if that wasn't found, the closest ancestor which was.
+	pub fn locate<S: AsRef<str>>(&self, path: &[S]) -> Option<Location> {
+		let mut path: Vec<String> = path.iter().map(|s| s.as_ref().to_string()).collect();
+		while !path.is_empty() {
+			if let Some(location) = self.locations.get(&path) {
+				return Some(*location);
+			}
+			path.pop();
+		}
+		None
+	}
+}
+
+/// Parse a YAML document, keeping the last of any duplicate keys, as the source map does.
+///
+/// serde_yaml_ng refuses to parse mappings with duplicate keys at all, so this lets the rest of a file
+/// still be checked once its duplicates have been reported.  Returns `None` if the YAML can't be parsed.
+pub fn parse_keeping_last(source: &str) -> Option<Value> {
+	let mut builder = ValueBuilder::default();
+	let mut parser = Parser::new_from_str(source);
+	parser.load(&mut builder, false).ok()?;
+	Some(builder.document.unwrap_or_default())
+}
+
+/// Builds a serde_yaml_ng value from parser events, overwriting any duplicate keys as they come.
+#[derive(Default)]
+struct ValueBuilder {
+	/// Containers still being filled, each with the key awaiting a value (for mappings) and its anchor id.
+	stack: Vec<(Value, Option<Value>, usize)>,
+	anchors: HashMap<usize, Value>,
+	document: Option<Value>,
+}
+
+impl ValueBuilder {
+	fn add(&mut self, value: Value, anchor: usize) {
+		if anchor > 0 {
+			self.anchors.insert(anchor, value.clone());
+		}
+		match self.stack.last_mut() {
+			Some((Value::Mapping(mapping), key, _)) => match key.take() {
+				Some(key) => { mapping.insert(key, value); },
+				None => *key = Some(value),
+			},
+			Some((Value::Sequence(items), ..)) => items.push(value),
+			_ => self.document = Some(value),
+		}
+	}
+}
+
+impl MarkedEventReceiver for ValueBuilder {
+	fn on_event(&mut self, event: Event, _mark: Marker) {
+		match event {
+			Event::Scalar(scalar, style, anchor, _) => {
+				// Resolve plain scalars (numbers, bools, nulls) the same way serde_yaml_ng would
+				let value = match style {
+					TScalarStyle::Plain => serde_yaml_ng::from_str(&scalar).unwrap_or(Value::String(scalar)),
+					_ => Value::String(scalar),
+				};
+				self.add(value, anchor);
+			},
+			Event::Alias(anchor) => {
+				let value = self.anchors.get(&anchor).cloned().unwrap_or_default();
+				self.add(value, 0);
+			},
+			Event::MappingStart(anchor, _) => self.stack.push((Value::Mapping(Mapping::new()), None, anchor)),
+			Event::SequenceStart(anchor, _) => self.stack.push((Value::Sequence(Vec::new()), None, anchor)),
+			Event::MappingEnd | Event::SequenceEnd => if let Some((value, _, anchor)) = self.stack.pop() {
+				self.add(value, anchor);
+			},
+			_ => {},
+		}
+	}
+}
+
+enum Container {
+	Mapping { key: Option<String> },
+	Sequence { index: usize },
+}
+
+#[derive(Default)]
+struct Builder {
+	stack: Vec<Container>,
+	locations: HashMap<Vec<String>, Location>,
+	duplicates: Vec<Duplicate>,
+}
+
+impl Builder {
+	fn path(&self) -> Vec<String> {
+		self.stack.iter().filter_map(|container| match container {
+			Container::Mapping { key } => key.clone(),
+			Container::Sequence { index } => Some(index.to_string()),
+		}).collect()
+	}
+
+	/// Called at the start of every node.  Returns true if the node is a mapping key rather than a value.
+	///
+	/// Mappings within a sequence aren't located here, as the parser marks a block mapping at its first `:`.
+	/// Instead they take the location of their first key.
+	fn start_node(&mut self, scalar: Option<&str>, mark: Marker, is_mapping: bool) -> bool {
+		match self.stack.last_mut() {
+			Some(Container::Mapping { key }) if key.is_none() => {
+				*key = Some(scalar.unwrap_or_default().to_string());
+				let mut path = self.path();
+				if let Some(first) = self.locations.get(&path).copied() {
+					// serde_yaml_ng keeps the last of any duplicates, so forget everything under the first one
+					self.locations.retain(|existing, _| !existing.starts_with(&path));
+					self.duplicates.push(Duplicate { path: path.clone(), first, second: mark.into() });
+				}
+				self.locations.insert(path.clone(), mark.into());
+				path.pop();
+				if !path.is_empty() {
+					self.locations.entry(path).or_insert(mark.into());
+				}
+				true
+			},
+			Some(Container::Sequence { .. }) if !is_mapping => {
+				let path = self.path();
+				self.locations.entry(path).or_insert(mark.into());
+				false
+			},
+			_ => false,
+		}
+	}
+
+	/// Called for scalars and aliases, which start and end in a single event.
+	fn leaf_node(&mut self, scalar: Option<&str>, mark: Marker) {
+		if !self.start_node(scalar, mark, false) {
+			self.end_value();
+		}
+	}
+
+	/// Called at the end of every value node, to move the parent container on to its next entry.
+	fn end_value(&mut self) {
+		match self.stack.last_mut() {
+			Some(Container::Mapping { key }) => *key = None,
+			Some(Container::Sequence { index }) => *index += 1,
+			None => {},
+		}
+	}
+}
+
+impl MarkedEventReceiver for Builder {
+	fn on_event(&mut self, event: Event, mark: Marker) {
+		match event {
+			Event::Scalar(value, ..) => self.leaf_node(Some(&value), mark),
+			Event::Alias(_) => self.leaf_node(None, mark),
+			Event::MappingStart(..) => {
+				self.start_node(None, mark, true);
+				self.stack.push(Container::Mapping { key: None });
+			},
+			Event::SequenceStart(..) => {
+				self.start_node(None, mark, false);
+				self.stack.push(Container::Sequence { index: 0 });
+			},
+			Event::MappingEnd | Event::SequenceEnd => {
+				self.stack.pop();
+				self.end_value();
+			},
+			_ => {},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn locates_top_level_and_nested_keys() {
+		let source = "\
+system1:
+  domain: example.com
+  public_ports:
+    - port: 25
+      protocol: tcp
+system2:
+  hosts: [host1, host2]
+";
+		let map = SourceMap::parse(source);
+		assert_eq!(map.locate(&["system1"]), Some(Location { line: 1, column: 1 }));
+		assert_eq!(map.locate(&["system1", "domain"]), Some(Location { line: 2, column: 3 }));
+		assert_eq!(map.locate(&["system1", "public_ports", "0"]), Some(Location { line: 4, column: 7 }));
+		assert_eq!(map.locate(&["system1", "public_ports", "0", "protocol"]), Some(Location { line: 5, column: 7 }));
+		assert_eq!(map.locate(&["system2"]), Some(Location { line: 6, column: 1 }));
+		assert_eq!(map.locate(&["system2", "hosts", "1"]), Some(Location { line: 7, column: 18 }));
+	}
+
+	#[test]
+	fn records_duplicate_keys() {
+		let source = "\
+system1:
+  domain: example.com
+  domain: example.org
+system2:
+  hosts: [host1]
+system1:
+  http_port: 8080
+";
+		let map = SourceMap::parse(source);
+		assert_eq!(map.duplicates(), &[
+			Duplicate { path: vec!["system1".to_string(), "domain".to_string()], first: Location { line: 2, column: 3 }, second: Location { line: 3, column: 3 } },
+			Duplicate { path: vec!["system1".to_string()], first: Location { line: 1, column: 1 }, second: Location { line: 6, column: 1 } },
+		]);
+		// Locations refer to the last definition, as that's the one which gets deserialised
+		assert_eq!(map.locate(&["system1", "http_port"]), Some(Location { line: 7, column: 3 }));
+		assert_eq!(map.locate(&["system1", "domain"]), Some(Location { line: 6, column: 1 }));
+	}
+
+	#[test]
+	fn parses_keeping_the_last_duplicate() {
+		let source = "\
+vol1:
+  description: First
+  skip_backup: true
+  description: Second
+  sizes: [1, 2.5, ~, 'quoted']
+";
+		let value = parse_keeping_last(source).unwrap();
+		let expected: Value = serde_yaml_ng::from_str("\
+vol1:
+  skip_backup: true
+  description: Second
+  sizes: [1, 2.5, ~, 'quoted']
+").unwrap();
+		assert_eq!(value, expected);
+	}
+
+	#[test]
+	fn falls_back_to_closest_ancestor() {
+		let map = SourceMap::parse("system1:\n  domain: example.com\n");
+		assert_eq!(map.locate(&["system1", "http_port"]), Some(Location { line: 1, column: 1 }));
+		assert_eq!(map.locate(&["system9"]), None);
+	}
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use arc_swap::ArcSwap;
+use axum::extract::FromRef;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::mpsc;
+use crate::data::Data;
+use crate::events::{ChangeFeed, ConfigChange};
+use crate::history::GitHistory;
+use crate::validation::{Issue, ValidationReport};
+
+/// How long to wait for a burst of file changes (eg a `git pull`) to settle before reloading.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Holds the currently served config, and swaps in a new version whenever it's reloaded.
+///
+/// Each request takes its own `Arc<Data>` snapshot, so a reload part-way through a request
+/// never mixes old and new config.
+pub struct ConfigStore {
+	/// The directory config is reloaded from, or `None` if the config is fixed for the lifetime of the store.
+	dir: Option<PathBuf>,
+	current: ArcSwap<Data>,
+	/// Why the most recent reload failed, if it did.  Cleared by the next successful reload.
+	reload_error: RwLock<Option<ValidationReport>>,
+	changes: ChangeFeed,
+	/// Held for the whole of a reload, so reloads triggered at once (by the watcher, `SIGHUP` and `/_admin/reload`)
+	/// take turns, rather than racing to swap in their config and publishing changes out of order.
+	reloading: Mutex<()>,
+	/// What was found the last time the config directory was loaded, for `/_info` to check against.
+	disk_state: Mutex<Option<DiskState>>,
+}
+
+/// The outcome of loading the config directory, and the files it was loaded from.
+struct DiskState {
+	fingerprint: String,
+	version: Result<String, ValidationReport>,
+}
+
+/// A hash of the name and content of every file in the config directory, to tell whether any have changed without parsing them.
+fn fingerprint(dir: &Path) -> String {
+	let mut files = Vec::new();
+	let mut dirs = vec![dir.to_path_buf()];
+	while let Some(dir) = dirs.pop() {
+		for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+			let path = entry.path();
+			if path.is_dir() {
+				dirs.push(path);
+			} else {
+				files.push(path);
+			}
+		}
+	}
+	files.sort();
+	let mut hasher = Sha256::new();
+	for file in files {
+		hasher.update(file.as_os_str().as_encoded_bytes());
+		hasher.update([0]);
+		hasher.update(std::fs::read(&file).unwrap_or_default());
+		hasher.update([0]);
+	}
+	format!("{:x}", hasher.finalize())
+}
+
+impl ConfigStore {
+	/// A store which serves the given config, and reloads from `dir` when asked to.
+	pub fn new<P: AsRef<Path>>(dir: P, data: Data) -> Self {
+		ConfigStore {
+			dir: Some(dir.as_ref().to_path_buf()),
+			current: ArcSwap::from_pointee(data),
+			reload_error: RwLock::new(None),
+			changes: ChangeFeed::default(),
+			reloading: Mutex::new(()),
+			disk_state: Mutex::new(None),
+		}
+	}
+
+	/// A store which always serves the given config.
+	pub fn fixed(data: Arc<Data>) -> Self {
+		ConfigStore {
+			dir: None,
+			current: ArcSwap::new(data),
+			reload_error: RwLock::new(None),
+			changes: ChangeFeed::default(),
+			reloading: Mutex::new(()),
+			disk_state: Mutex::new(None),
+		}
+	}
+
+	/// The config currently being served.
+	pub fn current(&self) -> Arc<Data> {
+		self.current.load_full()
+	}
+
+	/// Every change to the served config, for `/events` subscribers.
+	pub fn changes(&self) -> &ChangeFeed {
+		&self.changes
+	}
+
+	/// Why the most recent reload failed, or `None` if it succeeded (or there hasn't been one).
+	pub fn reload_error(&self) -> Option<ValidationReport> {
+		self.reload_error.read().unwrap().clone()
+	}
+
+	/// The version of the config directory as it is now, or why it fails to load, without serving it.
+	/// Returns `None` if the config is fixed, so there's no directory to load.
+	///
+	/// The config is only parsed again if a file in the directory has changed since it was last loaded (by this or a reload).
+	pub fn disk_version(&self) -> Option<Result<String, ValidationReport>> {
+		let dir = self.dir.as_ref()?;
+		let mut disk_state = self.disk_state.lock().unwrap();
+		let fingerprint = fingerprint(dir);
+		if let Some(state) = disk_state.as_ref().filter(|state| state.fingerprint == fingerprint) {
+			return Some(state.version.clone());
+		}
+		let version = Data::from_dir(dir).map(|data| data.version().to_string());
+		*disk_state = Some(DiskState { fingerprint, version: version.clone() });
+		Some(version)
+	}
+
+	fn record_disk_state(&self, fingerprint: String, loaded: &Result<Data, ValidationReport>) {
+		let version = loaded.as_ref().map(|data| data.version().to_string()).map_err(Clone::clone);
+		*self.disk_state.lock().unwrap() = Some(DiskState { fingerprint, version });
+	}
+
+	/// Load the config directory again, and start serving it if it's valid.
+	/// If it isn't, the last good config carries on being served, and the problems are kept for `/_info`.
+	pub fn reload(&self) -> Result<Arc<Data>, ValidationReport> {
+		let Some(dir) = &self.dir else {
+			return Ok(self.current());
+		};
+		let _reloading = self.reloading.lock().unwrap();
+		let fingerprint = fingerprint(dir);
+		let loaded = Data::from_dir(dir);
+		self.record_disk_state(fingerprint, &loaded);
+		match loaded {
+			Ok(data) => {
+				*self.reload_error.write().unwrap() = None;
+				let previous = self.current();
+				// Keep serving the same copy when nothing's changed, so its load time stays accurate for `Last-Modified`
+				if previous.version() == data.version() {
+					return Ok(previous);
+				}
+				for warning in data.warnings() {
+					eprintln!("WARNING: {}", warning);
+				}
+				let data = Arc::new(data);
+				self.changes.publish(ConfigChange::between(&previous, &data), || self.current.store(data.clone()));
+				Ok(data)
+			},
+			Err(report) => {
+				*self.reload_error.write().unwrap() = Some(report.clone());
+				Err(report)
+			},
+		}
+	}
+
+	/// Reload the config, and summarise what's being served as a result.
+	pub fn reload_outcome(&self) -> ReloadOutcome {
+		let (data, errors) = match self.reload() {
+			Ok(data) => (data, vec![]),
+			Err(report) => (self.current(), report.errors),
+		};
+		ReloadOutcome {
+			ok: errors.is_empty(),
+			version: data.version().to_string(),
+			counts: data.collections().iter().map(|collection| (collection.kind(), collection.count())).collect(),
+			errors,
+		}
+	}
+
+	/// Reload the config whenever anything in its directory changes.
+	///
+	/// Must be called from within a tokio runtime.  Changes stop being watched when the returned watcher is dropped.
+	pub fn watch(self: &Arc<Self>) -> notify::Result<RecommendedWatcher> {
+		let Some(dir) = &self.dir else {
+			return Err(notify::Error::generic("config store has no directory to watch"));
+		};
+		let (sender, mut receiver) = mpsc::unbounded_channel();
+		let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+			match event {
+				Ok(event) if event.kind.is_access() => {},
+				Ok(_) => { let _ = sender.send(()); },
+				Err(error) => eprintln!("Error watching config directory: {}", error),
+			}
+		})?;
+		watcher.watch(dir, RecursiveMode::Recursive)?;
+
+		let store = self.clone();
+		tokio::spawn(async move {
+			while receiver.recv().await.is_some() {
+				while let Ok(Some(())) = tokio::time::timeout(RELOAD_DEBOUNCE, receiver.recv()).await {}
+				// Reloads wait their turn, so keep them off the async runtime's threads
+				let reloaded = tokio::task::spawn_blocking({
+					let store = store.clone();
+					move || store.reload()
+				}).await.expect("reloading config panicked");
+				match reloaded {
+					Ok(data) => println!("Config changed on disk; reloaded {}", data.summary()),
+					Err(report) => eprintln!("Config changed on disk but failed to load; still serving the previous version\n{}", report),
+				}
+			}
+		});
+		Ok(watcher)
+	}
+}
+
+/// The result of an explicitly requested reload.
+///
+/// `version` and `counts` describe the config being served once the reload is done,
+/// which is the previous config if the reloaded one had any `errors`.
+#[derive(Serialize, Debug)]
+pub struct ReloadOutcome {
+	pub ok: bool,
+	pub version: String,
+	pub counts: BTreeMap<&'static str, usize>,
+	pub errors: Vec<Issue>,
+}
+
+impl fmt::Display for ReloadOutcome {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.ok {
+			write!(f, "Reloaded config version {}", self.version)
+		} else {
+			writeln!(f, "Failed to reload config; still serving version {}", self.version)?;
+			write!(f, "{}", ValidationReport { errors: self.errors.clone() })
+		}
+	}
+}
+
+/// The state shared by every route.
+///
+/// Handlers which only need the config can extract `State<Arc<Data>>`,
+/// which gives them a snapshot of whichever version is current when the request arrives.
+/// (The data endpoints are instead given theirs by `conditional_get`, which generates their validators from it.)
+#[derive(Clone)]
+pub struct AppState {
+	pub store: Arc<ConfigStore>,
+	/// The shared secret required by `/_admin` endpoints.  They're disabled if this is `None`.
+	pub admin_token: Option<String>,
+	/// Where to find past revisions of the config for `/at/{rev}` endpoints.  They're disabled if this is `None`.
+	pub history: Option<Arc<GitHistory>>,
+}
+
+impl AppState {
+	pub fn new(store: ConfigStore) -> Self {
+		AppState { store: Arc::new(store), admin_token: None, history: None }
+	}
+	pub fn with_admin_token(mut self, admin_token: Option<String>) -> Self {
+		self.admin_token = admin_token.filter(|token| !token.is_empty());
+		self
+	}
+	pub fn with_history(mut self, history: Option<GitHistory>) -> Self {
+		self.history = history.map(Arc::new);
+		self
+	}
+}
+
+impl FromRef<AppState> for Arc<Data> {
+	fn from_ref(state: &AppState) -> Self {
+		state.store.current()
+	}
+}
+
+impl FromRef<AppState> for Arc<ConfigStore> {
+	fn from_ref(state: &AppState) -> Self {
+		state.store.clone()
+	}
+}
@@ -1,32 +1,51 @@
 use std::sync::Arc;
 use axum::{
-	extract::{Query, Path, State},
+	extract::{Query, Path},
+	Extension,
 	response::Response,
 	http::header::HeaderMap,
 };
 use serde::Serialize;
 use crate::conneg::negotiate_response;
+use crate::registry::Record;
 
 /// Flat record returned by the `/systems/host/{host}/public-ports` endpoint.
 /// Contains the owning system's id alongside the port details so consumers
 /// (e.g. lucos_firewall) don't need to group by system themselves.
 #[derive(Serialize, Clone)]
-struct HostPublicPort {
-	system: String,
+struct HostPublicPort<'a> {
+	system: &'a str,
 	port: u16,
-	protocol: crate::data::Protocol,
-	purpose: String,
+	protocol: &'a crate::data::Protocol,
+	purpose: &'a str,
+}
+
+impl Record for HostPublicPort<'_> {
+	fn fields() -> Vec<&'static str> {
+		vec!["system", "port", "protocol", "purpose"]
+	}
 }
 
 #[derive(Serialize, Clone)]
-struct SystemWithSubdomain {
+struct SystemWithSubdomain<'a> {
 	#[serde(flatten)]
-	system: crate::data::System,
+	system: &'a crate::data::System,
 	subdomain: Option<String>,
 }
 
+impl Record for SystemWithSubdomain<'_> {
+	fn fields() -> Vec<&'static str> {
+		let mut fields = crate::data::System::fields();
+		fields.push("subdomain");
+		fields
+	}
+	fn defaults() -> Vec<(&'static str, serde_json::Value)> {
+		crate::data::System::defaults()
+	}
+}
+
 pub async fn all(
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
@@ -35,7 +54,7 @@ pub async fn all(
 
 pub async fn subdomain(
 	Path(root_domain): Path<String>,
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
@@ -54,7 +73,7 @@ pub async fn subdomain(
 }
 
 pub async fn http(
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
@@ -62,13 +81,16 @@ pub async fn http(
 	negotiate_response(&headers, params, http_systems)
 }
 
+/// Systems on an inactive host are left out unless `include_inactive=true` is given.
 pub async fn host(
 	Path(host): Path<String>,
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
+	Query(lifecycle): Query<crate::hosts::LifecycleParams>,
 ) -> Response {
-	let systems_on_host = data.get_systems_filtered(|system| system.hosts.contains(&host));
+	let host_included = lifecycle.include_inactive || data.is_host_active(&host);
+	let systems_on_host = data.get_systems_filtered(|system| host_included && system.hosts.contains(&host));
 	negotiate_response(&headers, params, systems_on_host)
 }
 
@@ -77,7 +99,7 @@ pub async fn host(
 /// Intended for consumption by lucos_firewall to generate iptables rules.
 pub async fn host_public_ports(
 	Path(host): Path<String>,
-	State(data): State<Arc<crate::data::Data>>,
+	Extension(data): Extension<Arc<crate::data::Data>>,
 	headers: HeaderMap,
 	params: Query<crate::conneg::Params>,
 ) -> Response {
@@ -85,12 +107,12 @@ pub async fn host_public_ports(
 		.get_systems_filtered(|system| system.hosts.contains(&host))
 		.into_iter()
 		.flat_map(|system| {
-			let system_id = system.id.clone().unwrap_or_default();
-			system.public_ports.into_iter().map(move |port| HostPublicPort {
-				system: system_id.clone(),
+			let system_id = system.id.as_deref().unwrap_or_default();
+			system.public_ports.iter().map(move |port| HostPublicPort {
+				system: system_id,
 				port: port.port,
-				protocol: port.protocol,
-				purpose: port.purpose,
+				protocol: &port.protocol,
+				purpose: &port.purpose,
 			})
 		})
 		.collect();
@@ -0,0 +1,84 @@
+use serde::Serialize;
+use std::fmt;
+
+/// A single problem found in the config, located as precisely as possible.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Issue {
+	/// The config file the problem was found in, relative to the config directory.
+	pub file: String,
+	pub line: Option<usize>,
+	pub column: Option<usize>,
+	/// The id of the system, volume, host etc which the problem relates to, if any.
+	pub entity: Option<String>,
+	pub message: String,
+}
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.file)?;
+		if let Some(line) = self.line {
+			write!(f, ":{}", line)?;
+			if let Some(column) = self.column {
+				write!(f, ":{}", column)?;
+			}
+		}
+		if let Some(entity) = &self.entity {
+			write!(f, ": {:?}", entity)?;
+		}
+		write!(f, ": {}", self.message)
+	}
+}
+
+/// Every problem found while loading the config.
+/// Loading carries on past the first problem, so a single run reports everything which needs fixing.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct ValidationReport {
+	pub errors: Vec<Issue>,
+}
+
+impl ValidationReport {
+	pub fn is_empty(&self) -> bool {
+		self.errors.is_empty()
+	}
+}
+
+impl fmt::Display for ValidationReport {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} problem(s) found in config:", self.errors.len())?;
+		for issue in &self.errors {
+			writeln!(f, "  {}", issue)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for ValidationReport {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn issue_display_includes_location() {
+		let issue = Issue {
+			file: "systems.yaml".to_string(),
+			line: Some(12),
+			column: Some(3),
+			entity: Some("lucos_mail".to_string()),
+			message: "unknown key `http-port`".to_string(),
+		};
+		assert_eq!(issue.to_string(), "systems.yaml:12:3: \"lucos_mail\": unknown key `http-port`");
+	}
+
+	#[test]
+	fn issue_display_without_location() {
+		let issue = Issue {
+			file: "hosts.yaml".to_string(),
+			line: None,
+			column: None,
+			entity: None,
+			message: "No such file or directory".to_string(),
+		};
+		assert_eq!(issue.to_string(), "hosts.yaml: No such file or directory");
+	}
+}
@@ -1,4 +1,6 @@
-use lucos_configy_api::routing::app;
+use lucos_configy_api::routing::{app, router};
+use lucos_configy_api::store::{AppState, ConfigStore};
+use lucos_configy_api::history::GitHistory;
 use lucos_configy_api::data::Data;
 use axum::{
 	body::Body,
@@ -47,7 +49,7 @@ system3:
 	writeln!(volumes_file, "
 vol1:
   description: Volume 1
-  recreate_effort: Low
+  recreate_effort: small
   backup_strategy: incremental
 vol2:
   description: Volume 2
@@ -70,6 +72,11 @@ host3:
   domain: h3.example.com
   ipv4: 1.1.1.3
   firewall_enforce: true
+host4:
+  domain: h4.example.com
+  ipv4: 1.1.1.4
+  serves_http: true
+  status: inactive
 ").unwrap();
 
 	let components_path = dir.path().join("components.yaml");
@@ -247,6 +254,61 @@ async fn test_hosts_http() {
 	assert_eq!(body[0]["id"], "host1");
 }
 
+#[tokio::test]
+async fn test_hosts_include_inactive() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts?include_inactive=true").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	let hosts = body.as_array().unwrap();
+	assert_eq!(hosts.len(), 4);
+
+	let host4 = hosts.iter().find(|h| h["id"] == "host4").unwrap();
+	assert_eq!(host4["status"], "inactive");
+	let host1 = hosts.iter().find(|h| h["id"] == "host1").unwrap();
+	assert_eq!(host1["status"], "active", "status should default to active when absent from YAML");
+}
+
+#[tokio::test]
+async fn test_hosts_http_include_inactive() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/http?include_inactive=true").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(body.as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_hosts_get_inactive_host() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	// Looking a host up by id still works when it's inactive
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/host4").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(body["status"], "inactive");
+}
+
 #[tokio::test]
 async fn test_hosts_can_reach_external_services_default() {
 	let data = create_mock_data().await;
@@ -532,6 +594,113 @@ async fn test_repositories_get_fields_filter() {
 	assert!(body.get("http_port").is_none());
 }
 
+async fn get_csv(uri: &str, accept: &str) -> String {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data), uri, &[("Accept", accept)]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "text/csv");
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_single_items_as_csv() {
+	let body = get_csv("/hosts/host1?fields=domain,ipv4,id", "text/csv").await;
+	assert_eq!(body, "domain,ipv4,id\nh1.example.com,1.1.1.1,host1\n");
+
+	let body = get_csv("/hosts/host1?fields=ipv4", "text/csv;header=absent").await;
+	assert_eq!(body, "1.1.1.1\n");
+
+	// Fields which aren't set are left empty
+	let body = get_csv("/hosts/host1?fields=id,ipv4_nat,ssh_gateway", "text/csv").await;
+	assert_eq!(body, "id,ipv4_nat,ssh_gateway\nhost1,,\n");
+
+	let body = get_csv("/repositories/comp1?fields=id,type,unsupervisedAgentCode", "text/csv").await;
+	assert_eq!(body, "id,type,unsupervisedAgentCode\ncomp1,component,true\n");
+}
+
+#[tokio::test]
+async fn test_single_item_csv_columns_default_to_every_field() {
+	let body = get_csv("/repositories/system1", "text/csv").await;
+	let mut lines = body.lines();
+	let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+	assert!(header.contains(&"id"));
+	assert!(header.contains(&"domain"));
+	assert!(header.contains(&"hosts"), "{header:?}");
+	assert!(header.contains(&"public_ports.port"), "{header:?}");
+	let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+	assert_eq!(row.len(), header.len());
+	assert_eq!(row[header.iter().position(|field| *field == "domain").unwrap()], "s1.example.com");
+	assert!(lines.next().is_none());
+}
+
+#[tokio::test]
+async fn test_csv_joins_lists_by_default() {
+	let body = get_csv("/systems?fields=id,hosts,public_ports", "text/csv").await;
+	assert_eq!(body, "\
+id,hosts,public_ports.port,public_ports.protocol,public_ports.purpose
+system1,host1,25;587,tcp;tcp,SMTP inbound;SMTP submission
+system2,host1;host2,,,
+system3,host2,53,udp,DNS
+");
+
+	let body = get_csv("/systems?fields=id,hosts&separator=%20", "text/csv;header=absent").await;
+	assert_eq!(body, "system1,host1\nsystem2,host1 host2\nsystem3,host2\n");
+}
+
+#[tokio::test]
+async fn test_csv_flattens_lists_into_numbered_columns() {
+	let body = get_csv("/systems?fields=id,hosts,public_ports&flatten=columns", "text/csv").await;
+	assert_eq!(body, "\
+id,hosts.0,hosts.1,public_ports.0.port,public_ports.0.protocol,public_ports.0.purpose,public_ports.1.port,public_ports.1.protocol,public_ports.1.purpose
+system1,host1,,25,tcp,SMTP inbound,587,tcp,SMTP submission
+system2,host1,host2,,,,,,
+system3,host2,,53,udp,DNS,,,
+");
+
+	// Fields within lists select that field from each item's columns
+	let body = get_csv("/systems?fields=id,public_ports.port&flatten=columns", "text/csv").await;
+	assert_eq!(body, "id,public_ports.0.port,public_ports.1.port\nsystem1,25,587\nsystem2,,\nsystem3,53,\n");
+}
+
+#[tokio::test]
+async fn test_csv_explodes_lists_into_rows() {
+	let body = get_csv("/systems?fields=id,hosts&flatten=explode", "text/csv").await;
+	assert_eq!(body, "id,hosts\nsystem1,host1\nsystem2,host1\nsystem2,host2\nsystem3,host2\n");
+
+	// Only the requested fields' lists add rows
+	let body = get_csv("/systems?fields=id,public_ports.port&flatten=explode", "text/csv").await;
+	assert_eq!(body, "id,public_ports.port\nsystem1,25\nsystem1,587\nsystem2,\nsystem3,53\n");
+}
+
+#[tokio::test]
+async fn test_csv_explode_is_limited() {
+	// 150 hosts and 100 ports on one system would explode into 15,000 rows
+	let dir = tempdir().unwrap();
+	let host_ids: Vec<String> = (0..150).map(|i| format!("host{i}")).collect();
+	let ports: String = (0..100).map(|i| format!("    - port: {}\n      protocol: tcp\n      purpose: Port {i}\n", 1000 + i)).collect();
+	write_reloadable_config(dir.path(), &format!("system1:\n  hosts: [{}]\n  public_ports:\n{}", host_ids.join(", "), ports));
+	let hosts: String = host_ids.iter().map(|id| format!("{id}:\n  domain: {id}.example.com\n")).collect();
+	std::fs::write(dir.path().join("hosts.yaml"), hosts).unwrap();
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	let response = get_with_headers(app(data.clone()), "/systems?flatten=explode", &[("Accept", "text/csv")]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert!(std::str::from_utf8(&body).unwrap().contains("15000 rows"));
+
+	// Selecting just one of the lists keeps it within the limit
+	let response = get_with_headers(app(data), "/systems?fields=id,hosts&flatten=explode", &[("Accept", "text/csv")]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_csv_rejects_unknown_flatten_mode() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data), "/systems?flatten=sideways", &[("Accept", "text/csv")]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_components_unsupervised_agent_code_set() {
 	let data = create_mock_data().await;
@@ -702,7 +871,7 @@ async fn test_all_turtle_contains_volumes() {
 
 	assert!(body.contains("/volumes#vol1>"));
 	assert!(body.contains("a configy:Volume"));
-	assert!(body.contains("configy:recreateEffort \"Low\""));
+	assert!(body.contains("configy:recreateEffort \"small\""));
 	assert!(body.contains("configy:backupStrategy \"incremental\""));
 	assert!(body.contains("configy:skipBackup true"));
 }
@@ -734,6 +903,60 @@ async fn test_all_json_fallback() {
 	assert!(body.get("scripts").is_some());
 }
 
+#[tokio::test]
+async fn test_info_metrics_count_every_entity_kind() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/_info").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	let metrics = &body["metrics"];
+	assert_eq!(metrics["system-count"]["value"], 3);
+	assert_eq!(metrics["volume-count"]["value"], 2);
+	assert_eq!(metrics["host-count"]["value"], 4);
+	assert_eq!(metrics["component-count"]["value"], 2);
+	assert_eq!(metrics["script-count"]["value"], 2);
+	assert_eq!(metrics["script-count"]["techDetail"], "The total number of local scripts configured");
+}
+
+#[tokio::test]
+async fn test_info_metrics_break_down_systems_and_volumes() {
+	let data = create_mock_data().await;
+	let body = get_json(app(data), "/_info").await;
+	let metrics = &body["metrics"];
+
+	assert_eq!(metrics["systems-on-host1"]["value"], 2);
+	assert_eq!(metrics["systems-on-host2"]["value"], 2);
+	assert_eq!(metrics["systems-on-host3"]["value"], 0);
+	assert_eq!(metrics["public-ports-on-host1"]["value"], 2);
+	assert_eq!(metrics["public-ports-on-host2"]["value"], 1);
+	assert_eq!(metrics["public-ports-on-host3"]["value"], 0);
+	// Inactive hosts aren't reported on
+	assert!(metrics.get("systems-on-host4").is_none());
+	assert!(metrics.get("public-ports-on-host4").is_none());
+	assert_eq!(metrics["skip-backup-volume-count"]["value"], 1);
+	assert_eq!(metrics["unsupervised-system-count"]["value"], 1);
+	assert!(metrics["config-age-seconds"]["value"].as_u64().unwrap() < 60);
+}
+
+#[tokio::test]
+async fn test_info_metrics_count_beyond_255() {
+	let dir = tempdir().unwrap();
+	let systems: String = (0..300).map(|i| format!("system{i}:\n  hosts: [host1]\n")).collect();
+	write_reloadable_config(dir.path(), &systems);
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	let body = get_json(app(data), "/_info").await;
+	assert_eq!(body["metrics"]["system-count"]["value"], 300);
+	assert_eq!(body["metrics"]["systems-on-host1"]["value"], 300);
+}
+
 // ── public_ports field and endpoint tests ──────────────────────────────────────
 
 #[tokio::test]
@@ -941,126 +1164,1695 @@ broken:
 	assert!(result.is_err(), "Expected config load to fail on port 0");
 }
 
-// ── /hosts/{host} endpoint tests ─────────────────────────────────────────────
+// ── host address tests ───────────────────────────────────────────────────────
 
-#[tokio::test]
-async fn test_hosts_get_by_id() {
-	let data = create_mock_data().await;
-	let app = app(data);
+fn write_addresses_config(dir: &std::path::Path, hosts: &str) {
+	std::fs::write(dir.join("systems.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.join("hosts.yaml"), hosts).unwrap();
+	std::fs::write(dir.join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.join("scripts.yaml"), "{}\n").unwrap();
+}
 
-	let response = app
-		.oneshot(Request::builder().uri("/hosts/host1").body(Body::empty()).unwrap())
+#[test]
+fn test_invalid_ip_addresses_fail_load() {
+	let dir = tempdir().unwrap();
+	write_addresses_config(dir.path(), "
+host1:
+  ipv4: 192.168.8.300
+host2:
+  ipv6: 2001:db8::g
+host3:
+  ipv4_nat: 2001:db8::1
+");
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on malformed addresses");
+	assert_eq!(report.errors.len(), 3, "Expected one error per bad address: {}", report);
+	assert_eq!(report.errors[0].entity.as_deref(), Some("host1"));
+	assert_eq!(report.errors[0].line, Some(3));
+}
+
+#[tokio::test]
+async fn test_host_addresses_are_canonical_and_classified() {
+	let dir = tempdir().unwrap();
+	write_addresses_config(dir.path(), "
+lan_host:
+  ipv4: 192.168.8.143
+public_host:
+  ipv4: 1.1.1.1
+  ipv6: 2001:0DB8:0000:0000:0000:0000:0000:0001
+ula_host:
+  ipv6: fd12:3456::1
+nat_host:
+  ipv6: fd12:3456::2
+  ipv4_nat: 1.1.1.2
+link_local_host:
+  ipv4: 169.254.10.1
+  ipv6: fe80::1
+loopback_host:
+  ipv4: 127.0.0.1
+  ipv6: ::1
+link_local_nat_host:
+  ipv4_nat: 169.254.10.2
+no_address_host:
+  domain: example.com
+");
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	let response = app(data)
+		.oneshot(Request::builder().uri("/hosts").body(Body::empty()).unwrap())
 		.await
 		.unwrap();
-
 	assert_eq!(response.status(), StatusCode::OK);
-	assert_eq!(response.headers().get("content-type").unwrap(), "application/json");
-
 	let body = response.into_body().collect().await.unwrap().to_bytes();
 	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	let host = |id: &str| body.as_array().unwrap().iter().find(|h| h["id"] == id).unwrap().clone();
+
+	let lan_host = host("lan_host");
+	assert_eq!(lan_host["ipv4_private"], true);
+	assert_eq!(lan_host["ipv6_private"], serde_json::Value::Null);
+	assert_eq!(lan_host["lan_only"], true);
+
+	let public_host = host("public_host");
+	assert_eq!(public_host["ipv6"], "2001:db8::1", "ipv6 should be served in its canonical form");
+	assert_eq!(public_host["ipv4_private"], false);
+	assert_eq!(public_host["ipv6_private"], false);
+	assert_eq!(public_host["lan_only"], false);
+
+	assert_eq!(host("ula_host")["ipv6_private"], true);
+	assert_eq!(host("ula_host")["lan_only"], true);
+	assert_eq!(host("nat_host")["lan_only"], false, "a public NAT address makes the host reachable");
+	assert_eq!(host("no_address_host")["lan_only"], false);
+	for id in ["link_local_host", "loopback_host"] {
+		assert_eq!(host(id)["ipv4_private"], true, "{id}");
+		assert_eq!(host(id)["ipv6_private"], true, "{id}");
+		assert_eq!(host(id)["lan_only"], true, "{id}");
+	}
+	assert_eq!(host("link_local_nat_host")["lan_only"], true);
+}
 
-	// Single object, not an array
-	assert!(body.as_object().is_some());
-	assert_eq!(body["id"], "host1");
-	assert_eq!(body["domain"], "h1.example.com");
-	assert_eq!(body["ipv4"], "1.1.1.1");
-	assert_eq!(body["serves_http"], true);
+#[test]
+fn test_derived_address_fields_cant_be_set_in_yaml() {
+	let dir = tempdir().unwrap();
+	write_addresses_config(dir.path(), "
+host1:
+  ipv4: 1.1.1.1
+  lan_only: true
+");
+
+	let report = Data::from_dir(dir.path()).err().expect("Derived fields should be rejected as unknown keys");
+	assert!(report.errors[0].message.contains("lan_only"));
 }
 
-#[tokio::test]
-async fn test_hosts_get_firewall_enforce_default_false() {
-	let data = create_mock_data().await;
-	let app = app(data);
+// ── enumerated field tests ───────────────────────────────────────────────────
 
-	let response = app
-		.oneshot(Request::builder().uri("/hosts/host1").body(Body::empty()).unwrap())
-		.await
-		.unwrap();
+#[test]
+fn test_invalid_enum_values_fail_load() {
+	let dir = tempdir().unwrap();
 
-	assert_eq!(response.status(), StatusCode::OK);
-	let body = response.into_body().collect().await.unwrap().to_bytes();
-	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	std::fs::write(dir.path().join("systems.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "
+vol1:
+  recreate_effort: Low
+vol2:
+  recreate_effort: small
+  backup_strategy: differential
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  shell_flavour: zsh
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
 
-	// host1 does not set firewall_enforce — should default to false
-	assert_eq!(body["firewall_enforce"], false,
-		"firewall_enforce should default to false when absent from YAML");
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unrecognised enum values");
+	assert_eq!(report.errors.len(), 3, "Expected one error per bad value: {}", report);
+	assert!(report.errors.iter().any(|e| e.entity.as_deref() == Some("vol1") && e.message.contains("Low")));
+	assert!(report.errors.iter().any(|e| e.entity.as_deref() == Some("vol2") && e.message.contains("differential")));
+	assert!(report.errors.iter().any(|e| e.entity.as_deref() == Some("host1") && e.message.contains("zsh")));
 }
 
 #[tokio::test]
-async fn test_hosts_get_firewall_enforce_true() {
+async fn test_enums_endpoint() {
 	let data = create_mock_data().await;
 	let app = app(data);
 
 	let response = app
-		.oneshot(Request::builder().uri("/hosts/host3").body(Body::empty()).unwrap())
+		.oneshot(Request::builder().uri("/enums").body(Body::empty()).unwrap())
 		.await
 		.unwrap();
 
 	assert_eq!(response.status(), StatusCode::OK);
 	let body = response.into_body().collect().await.unwrap().to_bytes();
 	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(body["backup_strategy"], serde_json::json!(["full-snapshot", "incremental"]));
+	assert_eq!(body["shell_flavour"], serde_json::json!(["gnu", "busybox"]));
+	assert_eq!(body["protocol"], serde_json::json!(["tcp", "udp"]));
+	assert_eq!(body["status"], serde_json::json!(["active", "inactive", "decommissioned"]));
+	assert_eq!(body["recreate_effort"].as_array().unwrap().len(), 7);
+	assert!(body["recreate_effort"].as_array().unwrap().contains(&serde_json::json!("considerable")));
+}
 
-	// host3 explicitly sets firewall_enforce: true
-	assert_eq!(body["firewall_enforce"], true,
-		"firewall_enforce: true should be honoured");
+// ── unknown key tests ────────────────────────────────────────────────────────
+
+#[test]
+fn test_unknown_key_fails_load() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "
+typo_volume:
+  description: Has a typo
+  skip_backups: true
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let err = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unknown key 'skip_backups'");
+	let message = err.to_string();
+	assert!(message.contains("volumes.yaml"), "Error should name the file: {}", message);
+	assert!(message.contains("typo_volume"), "Error should name the entity: {}", message);
+	assert!(message.contains("skip_backups"), "Error should name the key: {}", message);
 }
 
-#[tokio::test]
-async fn test_hosts_get_not_found() {
-	let data = create_mock_data().await;
-	let app = app(data);
+#[test]
+fn test_unknown_nested_key_fails_load() {
+	let dir = tempdir().unwrap();
 
-	let response = app
-		.oneshot(Request::builder().uri("/hosts/nonexistent").body(Body::empty()).unwrap())
-		.await
-		.unwrap();
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+  public_ports:
+    - port: 25
+      protocol: tcp
+      purpose: SMTP inbound
+      protcol: udp
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
 
-	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	let err = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unknown nested key");
+	assert!(err.to_string().contains("public_ports.0.protcol"), "Error should give the path to the key: {}", err);
 }
 
-#[tokio::test]
-async fn test_all_turtle_contains_firewall_enforce_ontology() {
-	let data = create_mock_data().await;
-	let app = app(data);
+#[test]
+fn test_unknown_key_lenient_mode_warns() {
+	use lucos_configy_api::data::Strictness;
 
-	let response = app
-		.oneshot(
-			Request::builder()
-				.uri("/all")
-				.header("Accept", "text/turtle")
-				.body(Body::empty())
-				.unwrap(),
-		)
-		.await
-		.unwrap();
+	let dir = tempdir().unwrap();
 
-	let body = response.into_body().collect().await.unwrap().to_bytes();
-	let body = std::str::from_utf8(&body).unwrap();
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  http-port: 8080
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
 
-	assert!(body.contains("configy:firewallEnforce"),
-		"Turtle output should declare firewallEnforce predicate in ontology");
+	let data = Data::from_dir_with_strictness(dir.path(), Strictness::Lenient).expect("Lenient load should succeed despite unknown key");
+	assert_eq!(data.system_count(), 1);
+	assert_eq!(data.warnings().len(), 1);
+	let warning = &data.warnings()[0];
+	assert_eq!(warning.file, "systems.yaml");
+	assert_eq!(warning.entity.as_deref(), Some("system1"));
+	assert_eq!(warning.line, Some(3));
+	assert!(warning.message.contains("http-port"));
 }
 
-#[tokio::test]
-async fn test_all_turtle_contains_firewall_enforce_data() {
-	let data = create_mock_data().await;
-	let app = app(data);
+#[test]
+fn test_validation_report_collects_every_error() {
+	let dir = tempdir().unwrap();
 
-	let response = app
-		.oneshot(
-			Request::builder()
-				.uri("/all")
-				.header("Accept", "text/turtle")
-				.body(Body::empty())
-				.unwrap(),
-		)
-		.await
-		.unwrap();
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+  public_ports:
+    - port: 80
+      protocol: ftp
+      purpose: Invalid protocol
+system2:
+  http_port: not-a-port
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "
+vol1:
+  skip_backups: true
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "{}\n").unwrap();
+	// components.yaml is deliberately missing
+	std::fs::write(dir.path().join("scripts.yaml"), "script1: [unclosed\n").unwrap();
 
-	let body = response.into_body().collect().await.unwrap().to_bytes();
-	let body = std::str::from_utf8(&body).unwrap();
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail");
+	assert_eq!(report.errors.len(), 5, "Expected every problem to be reported: {}", report);
 
-	// host3 has firewall_enforce: true — should appear in turtle output
+	let protocol = report.errors.iter().find(|e| e.message.contains("ftp")).unwrap();
+	assert_eq!(protocol.file, "systems.yaml");
+	assert_eq!(protocol.entity.as_deref(), Some("system1"));
+	assert_eq!(protocol.line, Some(6));
+	assert_eq!(protocol.column, Some(7));
+
+	let http_port = report.errors.iter().find(|e| e.entity.as_deref() == Some("system2")).unwrap();
+	assert_eq!(http_port.file, "systems.yaml");
+	assert_eq!(http_port.line, Some(9));
+
+	let unknown = report.errors.iter().find(|e| e.file == "volumes.yaml").unwrap();
+	assert_eq!(unknown.entity.as_deref(), Some("vol1"));
+	assert_eq!(unknown.line, Some(3));
+	assert!(unknown.message.contains("skip_backups"));
+
+	let missing = report.errors.iter().find(|e| e.file == "components.yaml").unwrap();
+	assert_eq!(missing.line, None);
+
+	let syntax = report.errors.iter().find(|e| e.file == "scripts.yaml").unwrap();
+	assert!(syntax.line.is_some(), "YAML syntax errors should carry a line number");
+}
+
+#[test]
+fn test_unknown_host_references_fail_load() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1, hots2]
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "
+vol1:
+  skip_backup_on_hosts:
+    - host3
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  ssh_gateway: host2
+host2:
+  domain: h2.example.com
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unknown host references");
+	assert_eq!(report.errors.len(), 2, "Expected one error per bad reference: {}", report);
+
+	let system_error = &report.errors[0];
+	assert_eq!(system_error.file, "systems.yaml");
+	assert_eq!(system_error.entity.as_deref(), Some("system1"));
+	assert_eq!(system_error.line, Some(3));
+	assert!(system_error.message.contains("\"hots2\""));
+
+	let volume_error = &report.errors[1];
+	assert_eq!(volume_error.file, "volumes.yaml");
+	assert_eq!(volume_error.entity.as_deref(), Some("vol1"));
+	assert_eq!(volume_error.line, Some(4));
+	assert!(volume_error.message.contains("\"host3\""));
+}
+
+#[test]
+fn test_unknown_ssh_gateway_fails_load() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  ssh_gateway: xwnig
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unknown ssh_gateway");
+	assert_eq!(report.errors.len(), 1);
+	assert_eq!(report.errors[0].entity.as_deref(), Some("host1"));
+	assert_eq!(report.errors[0].line, Some(3));
+	assert!(report.errors[0].message.contains("xwnig"));
+}
+
+#[test]
+fn test_duplicate_keys_fail_load() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+system2:
+  hosts: [host1]
+system1:
+  hosts: [host1]
+  http_port: 8080
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "
+vol1:
+  description: First
+  skip_backup: true
+  description: Second
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  domain: h1.example.com
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on duplicate keys");
+	assert_eq!(report.errors.len(), 2, "Expected one error per duplicate: {}", report);
+
+	let system_error = &report.errors[0];
+	assert_eq!(system_error.file, "systems.yaml");
+	assert_eq!(system_error.entity.as_deref(), Some("system1"));
+	assert_eq!(system_error.line, Some(6));
+	assert!(system_error.message.contains("line 2"), "Error should give both line numbers: {}", system_error);
+
+	let volume_error = &report.errors[1];
+	assert_eq!(volume_error.file, "volumes.yaml");
+	assert_eq!(volume_error.entity.as_deref(), Some("vol1"));
+	assert_eq!(volume_error.line, Some(5));
+	assert!(volume_error.message.contains("`description`"));
+	assert!(volume_error.message.contains("line 3"), "Error should give both line numbers: {}", volume_error);
+}
+
+#[test]
+fn test_duplicate_keys_dont_hide_other_errors_in_file() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+  http_port: 80
+  http_port: 8080
+system2:
+  hosts: [no_such_host]
+system3:
+  hosts: [host1]
+  http-port: 8080
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  domain: h1.example.com
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail");
+	let entities: Vec<_> = report.errors.iter().map(|issue| issue.entity.as_deref()).collect();
+	assert!(entities.contains(&Some("system1")), "Duplicate key should be reported: {}", report);
+	assert!(entities.contains(&Some("system2")), "Unknown host should still be reported: {}", report);
+	assert!(entities.contains(&Some("system3")), "Unknown key should still be reported: {}", report);
+}
+
+// ── split config directory tests ─────────────────────────────────────────────
+
+#[test]
+fn test_config_split_across_directory() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+").unwrap();
+	std::fs::create_dir(dir.path().join("systems")).unwrap();
+	std::fs::write(dir.path().join("systems").join("system2.yaml"), "
+system2:
+  hosts: [host1]
+").unwrap();
+	std::fs::write(dir.path().join("systems").join("group.yaml"), "
+system3:
+  hosts: [host1]
+system4:
+  hosts: [host1]
+").unwrap();
+	std::fs::write(dir.path().join("systems").join("README.md"), "Not config, so should be ignored\n").unwrap();
+	// volumes only exist as a directory
+	std::fs::create_dir(dir.path().join("volumes")).unwrap();
+	std::fs::write(dir.path().join("volumes").join("vol1.yaml"), "
+vol1:
+  description: Volume 1
+").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  domain: h1.example.com
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let data = Data::from_dir(dir.path()).unwrap_or_else(|report| panic!("Expected split config to load\n{}", report));
+	assert_eq!(data.system_count(), 4);
+	assert_eq!(data.volume_count(), 1);
+	let ids: Vec<String> = data.get_systems().iter().map(|s| s.id.clone().unwrap()).collect();
+	assert_eq!(ids, ["system1", "system2", "system3", "system4"]);
+}
+
+#[test]
+fn test_duplicate_ids_across_files_fail_load() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "
+system1:
+  hosts: [host1]
+").unwrap();
+	std::fs::create_dir(dir.path().join("systems")).unwrap();
+	std::fs::write(dir.path().join("systems").join("more.yaml"), "
+system2:
+  hosts: [host1]
+system1:
+  hosts: [hots1]
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "
+host1:
+  domain: h1.example.com
+").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on an id defined in two files");
+	assert_eq!(report.errors.len(), 1, "The second definition should be dropped, not checked: {}", report);
+	let error = &report.errors[0];
+	assert_eq!(error.file, "systems/more.yaml");
+	assert_eq!(error.entity.as_deref(), Some("system1"));
+	assert_eq!(error.line, Some(4));
+	assert!(error.message.contains("systems.yaml on line 2"), "Error should say where the first definition is: {}", error);
+}
+
+#[test]
+fn test_errors_in_split_files_name_the_file() {
+	let dir = tempdir().unwrap();
+
+	std::fs::write(dir.path().join("systems.yaml"), "{}\n").unwrap();
+	std::fs::create_dir(dir.path().join("systems")).unwrap();
+	std::fs::write(dir.path().join("systems").join("system1.yaml"), "
+system1:
+  hosts: [host9]
+").unwrap();
+	std::fs::write(dir.path().join("volumes.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("hosts.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.path().join("scripts.yaml"), "{}\n").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("Expected config load to fail on unknown host");
+	assert_eq!(report.errors.len(), 1);
+	assert_eq!(report.errors[0].file, "systems/system1.yaml");
+	assert_eq!(report.errors[0].line, Some(3));
+}
+
+// ── host lifecycle tests ─────────────────────────────────────────────────────
+
+fn write_lifecycle_config(dir: &std::path::Path) {
+	std::fs::write(dir.join("systems.yaml"), "
+active_system:
+  hosts: [active_host]
+stranded_system:
+  hosts: [inactive_host]
+").unwrap();
+	std::fs::write(dir.join("volumes.yaml"), "
+vol1:
+  skip_backup_on_hosts: [inactive_host]
+").unwrap();
+	std::fs::write(dir.join("hosts.yaml"), "
+active_host:
+  domain: active.example.com
+inactive_host:
+  domain: inactive.example.com
+  status: inactive
+").unwrap();
+	std::fs::write(dir.join("components.yaml"), "{}\n").unwrap();
+	std::fs::write(dir.join("scripts.yaml"), "{}\n").unwrap();
+}
+
+#[test]
+fn test_references_to_inactive_host_warn() {
+	let dir = tempdir().unwrap();
+	write_lifecycle_config(dir.path());
+
+	let data = Data::from_dir(dir.path()).expect("References to inactive hosts should only warn");
+	let warnings = data.warnings();
+	assert_eq!(warnings.len(), 2);
+	assert_eq!(warnings[0].file, "systems.yaml");
+	assert_eq!(warnings[0].entity.as_deref(), Some("stranded_system"));
+	assert!(warnings[0].message.contains("inactive host \"inactive_host\""));
+	assert_eq!(warnings[1].file, "volumes.yaml");
+	assert_eq!(warnings[1].entity.as_deref(), Some("vol1"));
+}
+
+#[test]
+fn test_references_to_decommissioned_host_fail_load() {
+	let dir = tempdir().unwrap();
+	write_lifecycle_config(dir.path());
+	std::fs::write(dir.path().join("hosts.yaml"), "
+active_host:
+  domain: active.example.com
+inactive_host:
+  domain: inactive.example.com
+  status: decommissioned
+").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("References to decommissioned hosts should fail the load");
+	assert_eq!(report.errors.len(), 2);
+	assert!(report.errors[0].message.contains("decommissioned host \"inactive_host\""));
+}
+
+#[test]
+fn test_invalid_host_status_fails_load() {
+	let dir = tempdir().unwrap();
+	write_lifecycle_config(dir.path());
+	std::fs::write(dir.path().join("hosts.yaml"), "
+active_host:
+  status: retired
+inactive_host:
+  status: inactive
+").unwrap();
+
+	let report = Data::from_dir(dir.path()).err().expect("An unrecognised status should fail the load");
+	assert_eq!(report.errors.len(), 1);
+	assert_eq!(report.errors[0].entity.as_deref(), Some("active_host"));
+}
+
+#[tokio::test]
+async fn test_systems_host_leaves_out_inactive_host() {
+	let dir = tempdir().unwrap();
+	write_lifecycle_config(dir.path());
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	let response = app(data.clone())
+		.oneshot(Request::builder().uri("/systems/host/inactive_host").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(body.as_array().unwrap().len(), 0);
+
+	let response = app(data)
+		.oneshot(Request::builder().uri("/systems/host/inactive_host?include_inactive=true").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+	assert_eq!(body.as_array().unwrap().len(), 1);
+	assert_eq!(body[0]["id"], "stranded_system");
+}
+
+// ── /hosts/{host} endpoint tests ─────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_hosts_get_by_id() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/host1").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "application/json");
+
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+	// Single object, not an array
+	assert!(body.as_object().is_some());
+	assert_eq!(body["id"], "host1");
+	assert_eq!(body["domain"], "h1.example.com");
+	assert_eq!(body["ipv4"], "1.1.1.1");
+	assert_eq!(body["serves_http"], true);
+}
+
+#[tokio::test]
+async fn test_hosts_get_firewall_enforce_default_false() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/host1").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+	// host1 does not set firewall_enforce — should default to false
+	assert_eq!(body["firewall_enforce"], false,
+		"firewall_enforce should default to false when absent from YAML");
+}
+
+#[tokio::test]
+async fn test_hosts_get_firewall_enforce_true() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/host3").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+	// host3 explicitly sets firewall_enforce: true
+	assert_eq!(body["firewall_enforce"], true,
+		"firewall_enforce: true should be honoured");
+}
+
+#[tokio::test]
+async fn test_hosts_get_not_found() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(Request::builder().uri("/hosts/nonexistent").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_all_turtle_contains_firewall_enforce_ontology() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(
+			Request::builder()
+				.uri("/all")
+				.header("Accept", "text/turtle")
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body = std::str::from_utf8(&body).unwrap();
+
+	assert!(body.contains("configy:firewallEnforce"),
+		"Turtle output should declare firewallEnforce predicate in ontology");
+}
+
+#[tokio::test]
+async fn test_all_turtle_contains_firewall_enforce_data() {
+	let data = create_mock_data().await;
+	let app = app(data);
+
+	let response = app
+		.oneshot(
+			Request::builder()
+				.uri("/all")
+				.header("Accept", "text/turtle")
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let body = std::str::from_utf8(&body).unwrap();
+
+	// host3 has firewall_enforce: true — should appear in turtle output
 	assert!(body.contains("configy:firewallEnforce true"),
 		"Turtle output should include firewallEnforce true for host3");
 }
+
+// ── config reload tests ──────────────────────────────────────────────────────
+
+/// Writes a minimal valid config, with the given systems.yaml, into `dir`.
+fn write_reloadable_config(dir: &std::path::Path, systems: &str) {
+	std::fs::write(dir.join("systems.yaml"), systems).unwrap();
+	std::fs::write(dir.join("hosts.yaml"), "host1:\n  domain: h1.example.com\n").unwrap();
+	for kind in ["volumes", "components", "scripts"] {
+		std::fs::write(dir.join(format!("{kind}.yaml")), "{}\n").unwrap();
+	}
+}
+
+async fn get_json(app: axum::Router, uri: &str) -> serde_json::Value {
+	let response = app
+		.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	let status = response.status();
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(status, StatusCode::OK, "GET {} responded with {}", uri, String::from_utf8_lossy(&body));
+	serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_reload_serves_new_config() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+
+	let systems = get_json(router(state.clone()), "/systems").await;
+	assert_eq!(systems.as_array().unwrap().len(), 2);
+	let info = get_json(router(state), "/_info").await;
+	assert_eq!(info["checks"]["config-reload"]["ok"], true);
+	assert_eq!(info["metrics"]["system-count"]["value"], 2);
+}
+
+#[tokio::test]
+async fn test_failed_reload_keeps_last_good_config() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [no_such_host]\n");
+	let Err(report) = state.store.reload() else { panic!("Expected reload to fail") };
+	assert!(report.to_string().contains("no_such_host"));
+
+	let systems = get_json(router(state.clone()), "/systems").await;
+	assert_eq!(systems.as_array().unwrap().len(), 1);
+	let info = get_json(router(state.clone()), "/_info").await;
+	assert_eq!(info["checks"]["config-reload"]["ok"], false);
+	assert!(info["checks"]["config-reload"]["debug"].as_str().unwrap().contains("no_such_host"));
+
+	// Fixing the config clears the failure
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+	let info = get_json(router(state), "/_info").await;
+	assert_eq!(info["checks"]["config-reload"]["ok"], true);
+	assert!(info["checks"]["config-reload"].get("debug").is_none());
+}
+
+#[tokio::test]
+async fn test_config_reloaded_when_files_change() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+	let _watcher = state.store.watch().unwrap();
+
+	std::fs::create_dir(dir.path().join("systems")).unwrap();
+	std::fs::write(dir.path().join("systems").join("system2.yaml"), "system2:\n  hosts: [host1]\n").unwrap();
+
+	for _ in 0..50 {
+		if state.store.current().system_count() == 2 {
+			return;
+		}
+		tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+	}
+	panic!("Config wasn't reloaded after a new file was added");
+}
+
+#[test]
+fn test_version_only_changes_with_content() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n  domain: s1.example.com\n");
+	let original = Data::from_dir(dir.path()).unwrap();
+	assert_eq!(original.version().len(), 64);
+
+	// Comments, formatting and key order don't affect the version
+	write_reloadable_config(dir.path(), "# A comment\nsystem1: {domain: s1.example.com, hosts: [host1]}\n");
+	assert_eq!(Data::from_dir(dir.path()).unwrap().version(), original.version());
+
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n  domain: s1.example.org\n");
+	assert_ne!(Data::from_dir(dir.path()).unwrap().version(), original.version());
+}
+
+async fn post_reload(state: AppState, token: Option<&str>) -> (StatusCode, String) {
+	let mut request = Request::builder().method("POST").uri("/_admin/reload");
+	if let Some(token) = token {
+		request = request.header("Authorization", format!("Bearer {token}"));
+	}
+	let response = router(state).oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+	let status = response.status();
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	(status, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_admin_reload_requires_token() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let store = ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap());
+	let state = AppState::new(store).with_admin_token(Some("s3cret".to_string()));
+
+	let (status, _) = post_reload(state.clone(), None).await;
+	assert_eq!(status, StatusCode::UNAUTHORIZED);
+	let (status, _) = post_reload(state.clone(), Some("guess")).await;
+	assert_eq!(status, StatusCode::UNAUTHORIZED);
+	let (status, _) = post_reload(state.clone(), Some("s3cres")).await;
+	assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+	// Without a token configured, the endpoint can't be used at all
+	let state = AppState { admin_token: None, ..state };
+	let (status, _) = post_reload(state, Some("")).await;
+	assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_admin_reload_reports_outcome() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let store = ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap());
+	let state = AppState::new(store).with_admin_token(Some("s3cret".to_string()));
+	let original_version = state.store.current().version().to_string();
+
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [host1]\n");
+	let (status, body) = post_reload(state.clone(), Some("s3cret")).await;
+	assert_eq!(status, StatusCode::OK);
+	let outcome: serde_json::Value = serde_json::from_str(&body).unwrap();
+	assert_eq!(outcome["ok"], true);
+	assert_eq!(outcome["counts"]["systems"], 2);
+	assert_eq!(outcome["counts"]["hosts"], 1);
+	assert_eq!(outcome["errors"].as_array().unwrap().len(), 0);
+	let new_version = outcome["version"].as_str().unwrap().to_string();
+	assert_ne!(new_version, original_version);
+	assert_eq!(state.store.current().version(), new_version);
+
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [no_such_host]\n");
+	let (status, body) = post_reload(state.clone(), Some("s3cret")).await;
+	assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+	let outcome: serde_json::Value = serde_json::from_str(&body).unwrap();
+	assert_eq!(outcome["ok"], false);
+	assert_eq!(outcome["version"], new_version, "The last good config should still be served");
+	assert_eq!(outcome["counts"]["systems"], 2);
+	assert_eq!(outcome["errors"][0]["file"], "systems.yaml");
+	assert_eq!(outcome["errors"][0]["entity"], "system2");
+}
+
+// ── past revision tests ──────────────────────────────────────────────────────
+
+/// Run a git command in the given repository, returning its trimmed stdout.
+fn git(repo: &std::path::Path, args: &[&str]) -> String {
+	let output = std::process::Command::new("git")
+		.arg("-C").arg(repo)
+		.args(["-c", "user.name=Test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+		.args(args)
+		.output()
+		.unwrap();
+	assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+	String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Creates a git repository with three commits of config, returning it along with the sha of each commit.
+fn create_config_repo() -> (tempfile::TempDir, Vec<String>) {
+	let repo = tempdir().unwrap();
+	let config = repo.path().join("config");
+	std::fs::create_dir(&config).unwrap();
+	git(repo.path(), &["init", "--quiet"]);
+	let mut commits = vec![];
+	for systems in [
+		"system1:\n  hosts: [host1]\n  domain: s1.example.com\n",
+		"system1:\n  hosts: [host1]\n  domain: s1.example.com\nsystem2:\n  hosts: [host1]\n",
+	] {
+		write_reloadable_config(&config, systems);
+		git(repo.path(), &["add", "-A"]);
+		git(repo.path(), &["commit", "--quiet", "-m", "Update config"]);
+		commits.push(git(repo.path(), &["rev-parse", "HEAD"]));
+	}
+	// The latest commit splits systems into a directory
+	std::fs::remove_file(config.join("systems.yaml")).unwrap();
+	std::fs::create_dir(config.join("systems")).unwrap();
+	std::fs::write(config.join("systems").join("system1.yaml"), "system1:\n  hosts: [host1]\n  domain: s1.example.org\n").unwrap();
+	std::fs::write(config.join("systems").join("system3.yaml"), "system3:\n  hosts: [host1]\n").unwrap();
+	git(repo.path(), &["add", "-A"]);
+	git(repo.path(), &["commit", "--quiet", "-m", "Split systems"]);
+	commits.push(git(repo.path(), &["rev-parse", "HEAD"]));
+	(repo, commits)
+}
+
+fn history_state(repo: &std::path::Path) -> AppState {
+	let data = Data::from_dir(repo.join("config")).unwrap();
+	AppState::new(ConfigStore::new(repo.join("config"), data))
+		.with_history(Some(GitHistory::new(repo, "config")))
+}
+
+#[tokio::test]
+async fn test_at_revision_serves_past_config() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+
+	let systems = get_json(router(state.clone()), &format!("/at/{}/systems", commits[0])).await;
+	assert_eq!(systems.as_array().unwrap().len(), 1);
+	assert_eq!(systems[0]["domain"], "s1.example.com");
+
+	let systems = get_json(router(state.clone()), "/at/HEAD~1/systems").await;
+	let ids: Vec<&str> = systems.as_array().unwrap().iter().map(|s| s["id"].as_str().unwrap()).collect();
+	assert_eq!(ids, ["system1", "system2"]);
+
+	let systems = get_json(router(state.clone()), "/at/HEAD/systems").await;
+	let ids: Vec<&str> = systems.as_array().unwrap().iter().map(|s| s["id"].as_str().unwrap()).collect();
+	assert_eq!(ids, ["system1", "system3"]);
+
+	let host = get_json(router(state), &format!("/at/{}/hosts/host1", &commits[1][..8])).await;
+	assert_eq!(host["domain"], "h1.example.com");
+}
+
+#[tokio::test]
+async fn test_at_revision_supports_fields_and_conneg() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+
+	let response = router(state)
+		.oneshot(
+			Request::builder()
+				.uri(format!("/at/{}/systems?fields=id,domain", commits[0]))
+				.header("Accept", "text/csv")
+				.body(Body::empty())
+				.unwrap(),
+		)
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "text/csv");
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(std::str::from_utf8(&body).unwrap(), "id,domain\nsystem1,s1.example.com\n");
+}
+
+#[tokio::test]
+async fn test_at_branch_with_slash() {
+	let (repo, commits) = create_config_repo();
+	git(repo.path(), &["branch", "feature/old-systems", &commits[0]]);
+	let state = history_state(repo.path());
+
+	// Slashes in the revision are percent-encoded, to keep them apart from the rest of the path
+	let systems = get_json(router(state), "/at/feature%2Fold-systems/systems").await;
+	assert_eq!(systems.as_array().unwrap().len(), 1);
+	assert_eq!(systems[0]["domain"], "s1.example.com");
+}
+
+#[tokio::test]
+async fn test_at_unknown_revision() {
+	let (repo, _) = create_config_repo();
+	let state = history_state(repo.path());
+
+	for rev in ["no-such-branch", "--output=oops", "HEAD~9"] {
+		let response = router(state.clone())
+			.oneshot(Request::builder().uri(format!("/at/{rev}/systems")).body(Body::empty()).unwrap())
+			.await
+			.unwrap();
+		assert_eq!(response.status(), StatusCode::NOT_FOUND, "revision {rev}");
+	}
+}
+
+#[tokio::test]
+async fn test_at_revision_without_repository() {
+	let data = create_mock_data().await;
+	let response = app(data)
+		.oneshot(Request::builder().uri("/at/HEAD/systems").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+// ── diff tests ───────────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_diff_between_revisions() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+
+	let diff = get_json(router(state), &format!("/diff?from={}&to={}", commits[1], commits[2])).await;
+	assert_eq!(diff["from"]["rev"], commits[1].as_str());
+	assert_eq!(diff["to"]["rev"], commits[2].as_str());
+	assert_eq!(diff["changes"], serde_json::json!([
+		{"kind": "systems", "id": "system1", "change": "modified", "fields": [
+			{"field": "domain", "before": "s1.example.com", "after": "s1.example.org"},
+		]},
+		{"kind": "systems", "id": "system2", "change": "removed", "fields": [
+			{"field": "hosts", "before": ["host1"], "after": null},
+			{"field": "public_ports", "before": [], "after": null},
+			{"field": "unsupervisedAgentCode", "before": false, "after": null},
+		]},
+		{"kind": "systems", "id": "system3", "change": "added", "fields": [
+			{"field": "hosts", "before": null, "after": ["host1"]},
+			{"field": "public_ports", "before": null, "after": []},
+			{"field": "unsupervisedAgentCode", "before": null, "after": false},
+		]},
+	]));
+}
+
+#[tokio::test]
+async fn test_diff_defaults_to_current_config() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+
+	let diff = get_json(router(state.clone()), "/diff?from=HEAD").await;
+	assert_eq!(diff["to"]["rev"], "current");
+	assert_eq!(diff["from"]["version"], diff["to"]["version"]);
+	assert_eq!(diff["changes"].as_array().unwrap().len(), 0);
+
+	let diff = get_json(router(state), &format!("/diff?from={}", commits[0])).await;
+	let ids: Vec<&str> = diff["changes"].as_array().unwrap().iter().map(|c| c["id"].as_str().unwrap()).collect();
+	assert_eq!(ids, ["system1", "system3"]);
+}
+
+#[tokio::test]
+async fn test_diff_as_text_and_yaml() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+	let uri = format!("/diff?from={}&to={}", commits[0], commits[1]);
+
+	let response = router(state.clone())
+		.oneshot(Request::builder().uri(&uri).header("Accept", "text/plain").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "text/plain; charset=utf-8");
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(std::str::from_utf8(&body).unwrap(), format!("\
+Changes from {} to {}:
+systems \"system2\": added
+  + hosts: [\"host1\"]
+  + public_ports: []
+  + unsupervisedAgentCode: false
+", commits[0], commits[1]));
+
+	let response = router(state)
+		.oneshot(Request::builder().uri(&uri).header("Accept", "application/x-yaml").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	let diff: serde_yaml_ng::Value = serde_yaml_ng::from_slice(&body).unwrap();
+	assert_eq!(diff["changes"][0]["id"], "system2");
+	assert_eq!(diff["changes"][0]["change"], "added");
+}
+
+#[tokio::test]
+async fn test_diff_unknown_revision() {
+	let (repo, _) = create_config_repo();
+	let state = history_state(repo.path());
+	let response = router(state)
+		.oneshot(Request::builder().uri("/diff?from=no-such-branch").body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+// ── /events tests ────────────────────────────────────────────────────────────
+
+/// Reads the next event from a Server-Sent Events body, skipping keep-alive comments.
+async fn next_event(body: &mut Body) -> String {
+	let mut event = String::new();
+	while !event.ends_with("\n\n") {
+		let frame = tokio::time::timeout(std::time::Duration::from_secs(5), body.frame())
+			.await
+			.expect("Timed out waiting for an event")
+			.unwrap()
+			.unwrap();
+		event.push_str(std::str::from_utf8(&frame.into_data().unwrap()).unwrap());
+		if event.starts_with(':') {
+			event.clear();
+		}
+	}
+	event
+}
+
+/// The value of the given field (eg `event`, `id` or `data`) in a Server-Sent Event.
+fn event_field<'a>(event: &'a str, field: &str) -> &'a str {
+	event.lines()
+		.find_map(|line| line.strip_prefix(&format!("{field}:")))
+		.map(|value| value.strip_prefix(' ').unwrap_or(value))
+		.unwrap_or_else(|| panic!("No {field} in event {event:?}"))
+}
+
+async fn subscribe(state: AppState, last_event_id: Option<&str>) -> Body {
+	let mut request = Request::builder().uri("/events");
+	if let Some(id) = last_event_id {
+		request = request.header("Last-Event-ID", id);
+	}
+	let response = router(state).oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "text/event-stream");
+	response.into_body()
+}
+
+#[tokio::test]
+async fn test_events_sent_when_config_changes() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+	let original_version = state.store.current().version().to_string();
+
+	let mut events = subscribe(state.clone(), None).await;
+	let event = next_event(&mut events).await;
+	assert_eq!(event_field(&event, "event"), "version");
+	assert_eq!(event_field(&event, "id"), original_version);
+
+	// Reloading unchanged config doesn't send anything
+	state.store.reload().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n  domain: s1.example.com\nsystem3:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+	let new_version = state.store.current().version().to_string();
+
+	let event = next_event(&mut events).await;
+	assert_eq!(event_field(&event, "event"), "change");
+	assert_eq!(event_field(&event, "id"), new_version);
+	let data: serde_json::Value = serde_json::from_str(event_field(&event, "data")).unwrap();
+	assert_eq!(data, serde_json::json!({
+		"version": new_version,
+		"previous_version": original_version,
+		"changes": [
+			{"kind": "systems", "id": "system1", "change": "modified"},
+			{"kind": "systems", "id": "system2", "change": "removed"},
+			{"kind": "systems", "id": "system3", "change": "added"},
+		],
+	}));
+}
+
+#[tokio::test]
+async fn test_events_resume_from_last_event_id() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+	let original_version = state.store.current().version().to_string();
+	for systems in ["system2:\n  hosts: [host1]\n", "system3:\n  hosts: [host1]\n"] {
+		write_reloadable_config(dir.path(), systems);
+		state.store.reload().unwrap();
+	}
+
+	// Both changes since the original version are replayed
+	let mut events = subscribe(state.clone(), Some(&original_version)).await;
+	let first = next_event(&mut events).await;
+	assert_eq!(event_field(&first, "event"), "change");
+	assert!(event_field(&first, "data").contains("\"system2\""));
+	let second = next_event(&mut events).await;
+	assert_eq!(event_field(&second, "id"), state.store.current().version());
+	assert!(event_field(&second, "data").contains("\"system3\""));
+
+	// An id which isn't known any more can't be resumed from
+	let mut events = subscribe(state.clone(), Some("long-forgotten")).await;
+	let event = next_event(&mut events).await;
+	assert_eq!(event_field(&event, "event"), "reset");
+	assert_eq!(event_field(&event, "id"), state.store.current().version());
+
+	// A client which is already up to date is just sent future changes
+	let mut events = subscribe(state.clone(), Some(state.store.current().version())).await;
+	write_reloadable_config(dir.path(), "system4:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+	let event = next_event(&mut events).await;
+	assert_eq!(event_field(&event, "event"), "change");
+	assert!(event_field(&event, "data").contains("\"system4\""));
+}
+
+#[tokio::test]
+async fn test_concurrent_reloads_publish_each_change_once() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+	let mut events = subscribe(state.clone(), Some(state.store.current().version())).await;
+
+	write_reloadable_config(dir.path(), "system2:\n  hosts: [host1]\n");
+	let reloads: Vec<_> = (0..8).map(|_| {
+		let store = state.store.clone();
+		std::thread::spawn(move || store.reload().unwrap())
+	}).collect();
+	for reload in reloads {
+		reload.join().unwrap();
+	}
+	write_reloadable_config(dir.path(), "system3:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+
+	let first = next_event(&mut events).await;
+	assert!(event_field(&first, "data").contains("\"system2\""));
+	let second = next_event(&mut events).await;
+	assert!(event_field(&second, "data").contains("\"system3\""), "The first change should only be sent once: {second}");
+}
+
+// ── config version tests ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_config_version_exposed_everywhere() {
+	let data = create_mock_data().await;
+	let version = data.version().to_string();
+
+	for uri in ["/systems", "/hosts/host1", "/hosts/no_such_host", "/_info", "/all", "/enums"] {
+		let response = app(data.clone())
+			.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+			.await
+			.unwrap();
+		assert_eq!(response.headers().get("X-Config-Version").unwrap(), version.as_str(), "header on {uri}");
+	}
+
+	let info = get_json(app(data.clone()), "/_info").await;
+	assert_eq!(info["config_version"], version);
+	let all = get_json(app(data), "/all").await;
+	assert_eq!(all["version"], version);
+}
+
+#[tokio::test]
+async fn test_config_version_at_revision() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+	let historic_version = Data::from_source(
+		&lucos_configy_api::source::GitSource::new(repo.path(), &commits[0], "config").unwrap(),
+		lucos_configy_api::data::Strictness::Strict,
+	).unwrap().version().to_string();
+	assert_ne!(historic_version, state.store.current().version());
+
+	let response = router(state)
+		.oneshot(Request::builder().uri(format!("/at/{}/systems", commits[0])).body(Body::empty()).unwrap())
+		.await
+		.unwrap();
+	assert_eq!(response.headers().get("X-Config-Version").unwrap(), historic_version.as_str());
+}
+
+// ── conditional request tests ────────────────────────────────────────────────
+
+async fn get_with_headers(app: axum::Router, uri: &str, headers: &[(&str, &str)]) -> axum::response::Response {
+	let mut request = Request::builder().uri(uri);
+	for (name, value) in headers {
+		request = request.header(*name, *value);
+	}
+	app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_etag_differs_per_representation() {
+	let data = create_mock_data().await;
+	let mut etags = std::collections::HashSet::new();
+	for (uri, accept) in [
+		("/systems", "application/json"),
+		("/systems", "text/csv"),
+		("/systems?fields=id", "application/json"),
+		("/hosts", "application/json"),
+	] {
+		let response = get_with_headers(app(data.clone()), uri, &[("Accept", accept)]).await;
+		assert_eq!(response.status(), StatusCode::OK);
+		assert_eq!(response.headers().get("Vary").unwrap(), "Accept");
+		assert!(response.headers().contains_key("Last-Modified"));
+		let etag = response.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+		assert!(etag.starts_with('"') && etag.ends_with('"'), "ETag {etag} should be strong");
+		assert!(etags.insert(etag), "ETag for {uri} as {accept} should be unique");
+	}
+
+	// The same representation of the same config gets the same ETag, however the format was asked for
+	let response = get_with_headers(app(data.clone()), "/systems", &[("Accept", "text/csv")]).await;
+	assert!(etags.contains(response.headers().get("ETag").unwrap().to_str().unwrap()));
+	let response = get_with_headers(app(data.clone()), "/systems", &[("Accept", "text/csv, application/json;q=0.5")]).await;
+	assert!(etags.contains(response.headers().get("ETag").unwrap().to_str().unwrap()));
+}
+
+#[tokio::test]
+async fn test_if_none_match_returns_not_modified() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	let response = get_with_headers(router(state.clone()), "/systems", &[]).await;
+	let etag = response.headers().get("ETag").unwrap().to_str().unwrap().to_string();
+
+	let response = get_with_headers(router(state.clone()), "/systems", &[("If-None-Match", &etag)]).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+	assert_eq!(response.headers().get("ETag").unwrap(), etag.as_str());
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert!(body.is_empty());
+
+	// Reloading unchanged config keeps the ETag valid
+	state.store.reload().unwrap();
+	let response = get_with_headers(router(state.clone()), "/systems", &[("If-None-Match", &etag)]).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+	// A different format isn't the same representation
+	let response = get_with_headers(router(state.clone()), "/systems", &[("If-None-Match", &etag), ("Accept", "text/csv")]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	// Once the config changes, the full response is sent again
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+	let response = get_with_headers(router(state), "/systems", &[("If-None-Match", &etag)]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_ne!(response.headers().get("ETag").unwrap(), etag.as_str());
+}
+
+#[tokio::test]
+async fn test_if_modified_since_returns_not_modified() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data.clone()), "/hosts/host1", &[]).await;
+	let last_modified = response.headers().get("Last-Modified").unwrap().to_str().unwrap().to_string();
+
+	let response = get_with_headers(app(data.clone()), "/hosts/host1", &[("If-Modified-Since", &last_modified)]).await;
+	assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+	let response = get_with_headers(app(data.clone()), "/hosts/host1", &[("If-Modified-Since", "Sat, 01 Jan 2000 00:00:00 GMT")]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+
+	// If-None-Match takes precedence over If-Modified-Since
+	let response = get_with_headers(app(data), "/hosts/host1", &[("If-Modified-Since", &last_modified), ("If-None-Match", "\"stale\"")]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_errors_have_no_etag() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data), "/hosts/no_such_host", &[]).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+	assert!(!response.headers().contains_key("ETag"));
+}
+
+#[tokio::test]
+async fn test_conditional_requests_dont_hide_errors() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data.clone()), "/systems", &[]).await;
+	let last_modified = response.headers().get("Last-Modified").unwrap().to_str().unwrap().to_string();
+
+	for (uri, accept, status) in [
+		("/hosts/no_such_host", "application/json", StatusCode::NOT_FOUND),
+		("/systems?fields=no_such_field", "application/json", StatusCode::BAD_REQUEST),
+		("/systems", "image/png", StatusCode::NOT_ACCEPTABLE),
+	] {
+		for condition in [("If-None-Match", "*"), ("If-Modified-Since", last_modified.as_str())] {
+			let response = get_with_headers(app(data.clone()), uri, &[condition, ("Accept", accept)]).await;
+			assert_eq!(response.status(), status, "{uri} as {accept} with {condition:?}");
+		}
+	}
+}
+
+// ── /_info check tests ───────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_info_checks_pass_when_serving_config_on_disk() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	let info = get_json(router(state), "/_info").await;
+	for check in ["config-valid", "config-current", "config-reload", "config-warnings"] {
+		assert_eq!(info["checks"][check]["ok"], true, "check {check}");
+		assert!(info["checks"][check]["techDetail"].is_string(), "check {check}");
+		assert!(info["checks"][check].get("debug").is_none(), "check {check}");
+	}
+}
+
+#[tokio::test]
+async fn test_info_checks_fail_when_disk_differs() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	// Changed on disk, but not yet reloaded
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem2:\n  hosts: [host1]\n");
+	let info = get_json(router(state.clone()), "/_info").await;
+	assert_eq!(info["checks"]["config-valid"]["ok"], true);
+	assert_eq!(info["checks"]["config-current"]["ok"], false);
+	assert!(info["checks"]["config-current"]["debug"].as_str().unwrap().contains("the config on disk is version"));
+
+	// Invalid on disk
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [no_such_host]\n");
+	let info = get_json(router(state.clone()), "/_info").await;
+	assert_eq!(info["checks"]["config-valid"]["ok"], false);
+	assert!(info["checks"]["config-valid"]["debug"].as_str().unwrap().contains("no_such_host"));
+	assert_eq!(info["checks"]["config-current"]["ok"], false);
+
+	// Fixed on disk and reloaded
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\nsystem3:\n  hosts: [host1]\n");
+	state.store.reload().unwrap();
+	let info = get_json(router(state), "/_info").await;
+	assert_eq!(info["checks"]["config-valid"]["ok"], true);
+	assert_eq!(info["checks"]["config-current"]["ok"], true);
+}
+
+#[tokio::test]
+async fn test_info_check_for_warnings() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host2]\n");
+	std::fs::write(dir.path().join("hosts.yaml"), "host1:\n  domain: h1.example.com\nhost2:\n  status: inactive\n").unwrap();
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	// Warnings are listed and counted, but don't fail the check
+	let info = get_json(router(state), "/_info").await;
+	assert_eq!(info["checks"]["config-warnings"]["ok"], true);
+	let debug = info["checks"]["config-warnings"]["debug"].as_str().unwrap();
+	assert!(debug.starts_with("1 warning(s) found in config:"), "{debug}");
+	assert!(debug.contains("host2"), "{debug}");
+	assert_eq!(info["metrics"]["config-warning-count"]["value"], 1);
+}
+
+// ── content negotiation tests ──────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_unacceptable_types_get_406_listing_supported_types() {
+	let data = create_mock_data().await;
+	for (uri, supported) in [
+		("/systems", "application/json, application/x-yaml, text/csv"),
+		("/hosts/host1", "application/json, application/x-yaml, text/csv"),
+		("/all", "text/turtle, application/json"),
+	] {
+		let response = get_with_headers(app(data.clone()), uri, &[("Accept", "text/html, application/json;q=0")]).await;
+		assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE, "{uri}");
+		let body = response.into_body().collect().await.unwrap().to_bytes();
+		let body = std::str::from_utf8(&body).unwrap();
+		assert!(body.ends_with(&format!("Supported types: {}", supported)), "{uri}: {body}");
+	}
+}
+
+#[tokio::test]
+async fn test_exclusions_override_wildcards() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data), "/systems", &[("Accept", "application/json;q=0, */*;q=0.5, text/*;q=0.1")]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "application/yaml");
+}
+
+// ── format selection tests ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_format_chosen_by_extension() {
+	let data = create_mock_data().await;
+	for (uri, content_type) in [
+		("/systems.csv", "text/csv"),
+		("/systems.json", "application/json"),
+		("/hosts/host1.yaml", "application/yaml"),
+		("/repositories/comp1.csv", "text/csv"),
+		("/all.ttl", "text/turtle; charset=utf-8"),
+	] {
+		// The extension takes precedence over the Accept header
+		let response = get_with_headers(app(data.clone()), uri, &[("Accept", "application/x-yaml;q=0.1, application/json")]).await;
+		assert_eq!(response.status(), StatusCode::OK, "{uri}");
+		assert_eq!(response.headers().get("content-type").unwrap(), content_type, "{uri}");
+	}
+
+	let body = get_csv("/hosts/host1.csv?fields=id,domain", "application/json").await;
+	assert_eq!(body, "id,domain\nhost1,h1.example.com\n");
+}
+
+#[tokio::test]
+async fn test_format_chosen_by_param() {
+	let body = get_csv("/systems?format=csv&header=absent&fields=id", "application/json").await;
+	assert_eq!(body, "system1\nsystem2\nsystem3\n");
+
+	let body = get_csv("/systems.csv?header=absent&fields=id", "").await;
+	assert_eq!(body, "system1\nsystem2\nsystem3\n");
+
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data.clone()), "/all?format=turtle", &[]).await;
+	assert_eq!(response.headers().get("content-type").unwrap(), "text/turtle; charset=utf-8");
+
+	// The param takes precedence over the extension
+	let response = get_with_headers(app(data), "/hosts/host1.csv?format=yaml", &[]).await;
+	assert_eq!(response.headers().get("content-type").unwrap(), "application/yaml");
+}
+
+#[tokio::test]
+async fn test_format_unknown_or_unsupported() {
+	let data = create_mock_data().await;
+	let response = get_with_headers(app(data.clone()), "/systems?format=xml", &[]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(std::str::from_utf8(&body).unwrap(), "Unknown format \"xml\".  Known formats: json, yaml, csv, turtle");
+
+	// Extensions and format names are each only accepted where they're documented
+	let response = get_with_headers(app(data.clone()), "/all?format=ttl", &[]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	let response = get_with_headers(app(data.clone()), "/all.turtle", &[]).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let response = get_with_headers(app(data.clone()), "/systems?format=csv&header=sometimes", &[]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	// A known format which the endpoint doesn't offer
+	let response = get_with_headers(app(data.clone()), "/systems.ttl", &[]).await;
+	assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+
+	// Anything else after a dot is left alone
+	let response = get_with_headers(app(data), "/systems/subdomain/other.net", &[]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_format_only_chosen_for_endpoints_with_formats() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	let state = AppState::new(ConfigStore::new(dir.path(), Data::from_dir(dir.path()).unwrap()));
+
+	// Params which mean something else to other endpoints, or nothing at all, are left to them
+	let response = get_with_headers(router(state.clone()), "/_info?format=xml", &[]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let response = get_with_headers(router(state.clone()), "/diff?from=HEAD&format=xml", &[]).await;
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert!(!std::str::from_utf8(&body).unwrap().contains("Unknown format"));
+
+	// Nor are their paths rewritten
+	let response = get_with_headers(router(state.clone()), "/_info.json", &[]).await;
+	assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+	let response = get_with_headers(router(state), "/enums.yaml", &[]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	assert_eq!(response.headers().get("content-type").unwrap(), "application/yaml");
+}
+
+#[tokio::test]
+async fn test_format_chosen_by_extension_at_past_revision() {
+	let (repo, commits) = create_config_repo();
+	let state = history_state(repo.path());
+	let response = get_with_headers(router(state), &format!("/at/{}/systems.csv?fields=id", commits[1]), &[]).await;
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(std::str::from_utf8(&body).unwrap(), "id\nsystem1\nsystem2\n");
+}
+
+// ── fields selection tests ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_fields_select_within_nested_lists() {
+	let data = create_mock_data().await;
+	let body = get_json(app(data), "/systems?fields=id,public_ports.port").await;
+	assert_eq!(body[0], serde_json::json!({ "id": "system1", "public_ports": [{ "port": 25 }, { "port": 587 }] }));
+	assert_eq!(body[1], serde_json::json!({ "id": "system2", "public_ports": [] }));
+}
+
+#[tokio::test]
+async fn test_fields_exclusions() {
+	let data = create_mock_data().await;
+	let host = get_json(app(data.clone()), "/hosts/host1?fields=-ipv6,-ipv4_nat").await;
+	assert_eq!(host["domain"], "h1.example.com");
+	assert!(host.get("ipv6").is_none());
+	assert!(host.get("ipv4_nat").is_none());
+
+	let body = get_csv("/systems?fields=-domain,-http_port,-unsupervisedAgentCode,-public_ports", "text/csv").await;
+	assert_eq!(body.lines().next().unwrap(), "hosts,id");
+}
+
+#[tokio::test]
+async fn test_unknown_fields_are_rejected() {
+	let data = create_mock_data().await;
+	for uri in ["/systems?fields=id,hostz", "/hosts/host1?fields=-ipv7", "/systems?fields=public_ports.prot"] {
+		let response = get_with_headers(app(data.clone()), uri, &[("Accept", "text/csv")]).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+	}
+	let response = get_with_headers(app(data.clone()), "/systems?fields=id,hostz,domian", &[]).await;
+	let body = response.into_body().collect().await.unwrap().to_bytes();
+	assert_eq!(std::str::from_utf8(&body).unwrap(), "Unknown field(s) in `fields`: hostz, domian");
+
+	// Fields are checked against what the records can have, so even with nothing to respond with,
+	// or a field which happens to be null, typos are caught
+	for uri in ["/systems/host/no_such_host?fields=anything", "/hosts/host1?fields=backup_root.anything"] {
+		let response = get_with_headers(app(data.clone()), uri, &[]).await;
+		assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+	}
+	let body = get_json(app(data), "/systems/host/no_such_host?fields=id,public_ports.port").await;
+	assert_eq!(body, serde_json::json!([]));
+}
+
+/// Every field within a value, as dotted paths.
+fn field_paths(value: &serde_json::Value, path: &str, paths: &mut std::collections::BTreeSet<String>) {
+	match value {
+		serde_json::Value::Object(map) => for (key, value) in map {
+			let child = if path.is_empty() { key.clone() } else { format!("{path}.{key}") };
+			paths.insert(child.clone());
+			field_paths(value, &child, paths);
+		},
+		serde_json::Value::Array(items) => for item in items {
+			field_paths(item, path, paths);
+		},
+		_ => {},
+	}
+}
+
+#[tokio::test]
+async fn test_every_field_returned_can_be_selected() {
+	let data = create_mock_data().await;
+	for uri in [
+		"/systems", "/systems/subdomain/example.com", "/systems/host/host1/public-ports",
+		"/hosts?include_inactive=true", "/hosts/host1", "/volumes", "/components", "/scripts",
+		"/repositories/system1", "/repositories/comp1", "/repositories/script1", "/enums",
+	] {
+		let body = get_json(app(data.clone()), uri).await;
+		let mut paths = std::collections::BTreeSet::new();
+		match &body {
+			serde_json::Value::Array(records) => records.iter().for_each(|record| field_paths(record, "", &mut paths)),
+			record => field_paths(record, "", &mut paths),
+		}
+		let fields: Vec<String> = paths.into_iter().collect();
+		let separator = if uri.contains('?') { '&' } else { '?' };
+		let selected = get_json(app(data.clone()), &format!("{uri}{separator}fields={}", fields.join(","))).await;
+		assert_eq!(selected, body, "{uri}");
+	}
+}
+
+// ── nulls and defaults tests ───────────────────────────────────────────────────
+
+#[tokio::test]
+async fn test_nulls_omitted_on_request() {
+	let data = create_mock_data().await;
+	let host = get_json(app(data.clone()), "/hosts/host1").await;
+	assert!(host["backup_root"].is_null());
+	assert!(host.get("backup_root").is_some());
+
+	let host = get_json(app(data.clone()), "/hosts/host1?nulls=omit").await;
+	assert!(host.get("backup_root").is_none());
+	assert!(host.get("ssh_gateway").is_none());
+	assert!(host.get("ipv6").is_none());
+	assert_eq!(host["domain"], "h1.example.com");
+	assert_eq!(host["firewall_enforce"], false);
+
+	let systems = get_json(app(data), "/systems?nulls=omit").await;
+	assert!(systems[2].get("http_port").is_none());
+	assert_eq!(systems[0]["http_port"], 80);
+
+	let body = get_csv("/hosts/host1?fields=id,ssh_gateway&nulls=omit", "text/csv").await;
+	assert_eq!(body, "id,ssh_gateway\nhost1,\n");
+}
+
+#[tokio::test]
+async fn test_defaults_filled_in_on_request() {
+	let data = create_mock_data().await;
+	let host = get_json(app(data.clone()), "/hosts/host1?defaults=explicit").await;
+	assert_eq!(host["backup_root"], "/srv/backups/");
+	assert_eq!(host["shell_flavour"], "gnu");
+	// Fields without a documented default are left alone
+	assert!(host["ssh_gateway"].is_null());
+
+	let host = get_json(app(data.clone()), "/hosts/host1?defaults=explicit&nulls=omit&fields=backup_root,ssh_gateway").await;
+	assert_eq!(host, serde_json::json!({ "backup_root": "/srv/backups/" }));
+
+	let response = get_with_headers(app(data), "/hosts?nulls=sometimes", &[]).await;
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_defaults_only_fill_fields_of_the_record_type() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	std::fs::write(dir.path().join("hosts.yaml"), "host1: {}\n").unwrap();
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	// Systems have no documented defaults, so none of their fields (or those nested within them) are filled in
+	let systems = get_json(app(data.clone()), "/systems?defaults=explicit").await;
+	assert_eq!(systems, get_json(app(data.clone()), "/systems").await);
+
+	let all = get_json(app(data), "/all?defaults=explicit&nulls=omit").await;
+	assert_eq!(all["hosts"][0]["backup_root"], "/srv/backups/");
+	assert_eq!(all["hosts"][0]["shell_flavour"], "gnu");
+	assert!(all["hosts"][0].get("ssh_gateway").is_none());
+	assert!(all["systems"][0].get("domain").is_none());
+	assert!(all["systems"][0].get("backup_root").is_none());
+}
+
+#[tokio::test]
+async fn test_defaults_dont_override_set_values() {
+	let dir = tempdir().unwrap();
+	write_reloadable_config(dir.path(), "system1:\n  hosts: [host1]\n");
+	std::fs::write(dir.path().join("hosts.yaml"), "host1:\n  backup_root: /mnt/backups/\n  shell_flavour: busybox\n").unwrap();
+	let data = Arc::new(Data::from_dir(dir.path()).unwrap());
+
+	let host = get_json(app(data), "/hosts/host1?defaults=explicit").await;
+	assert_eq!(host["backup_root"], "/mnt/backups/");
+	assert_eq!(host["shell_flavour"], "busybox");
+}
@@ -1,4 +1,4 @@
-use lucos_configy_api::data::Data;
+use lucos_configy_api::data::{config_files, Data, RecreateEffort};
 use std::path::{Path, PathBuf};
 use serde_yaml_ng::Value;
 
@@ -12,7 +12,7 @@ fn config_dir() -> PathBuf {
 }
 
 fn load_test_data() -> Data {
-	Data::from_dir(&config_dir()).expect("Failed to load config")
+	Data::from_dir(config_dir()).unwrap_or_else(|report| panic!("Failed to load config\n{}", report))
 }
 
 #[test]
@@ -43,6 +43,13 @@ fn validate_config_files() {
 	}
 }
 
+#[test]
+fn config_has_no_warnings() {
+	let data = load_test_data();
+	let warnings: Vec<String> = data.warnings().iter().map(|w| w.to_string()).collect();
+	assert!(warnings.is_empty(), "Config has warnings:\n{}", warnings.join("\n"));
+}
+
 #[test]
 fn repository_ids_are_unique_across_types() {
 	let data = load_test_data();
@@ -60,94 +67,52 @@ fn repository_ids_are_unique_across_types() {
 	}
 }
 
-/// Recognised `recreate_effort` ids. The canonical source of truth for this set
-/// is `lucos_backups/src/effort_labels.yaml` — lucos_backups looks each volume's
-/// `recreate_effort` up against it unguarded, so an unrecognised (or missing)
-/// value crashes host-tracking for an entire host in production. This is a
-/// deliberate duplication: if lucos_backups ever adds or renames an effort label,
-/// this constant must be updated in lockstep. See lucas42/lucos_configy#221.
-const RECOGNISED_EFFORTS: [&str; 7] = [
-	"small",
-	"considerable",
-	"huge",
-	"automatic",
-	"tolerable",
-	"remote",
-	"unknown",
-];
-
-#[test]
-fn recreate_effort_is_present_and_recognised() {
-	let data = load_test_data();
-
-	for volume in data.get_volumes() {
-		let id = volume.id.as_deref().unwrap_or("<unknown>");
-		match &volume.recreate_effort {
-			None => panic!(
-				"Volume {:?} is missing recreate_effort; it must be one of {:?}",
-				id, RECOGNISED_EFFORTS
-			),
-			Some(effort) => assert!(
-				RECOGNISED_EFFORTS.contains(&effort.as_str()),
-				"Volume {:?} has unrecognised recreate_effort {:?}; it must be one of {:?}",
-				id, effort, RECOGNISED_EFFORTS
-			),
-		}
-	}
-}
-
-/// Recognised `backup_strategy` ids. The canonical source of truth is
-/// `lucos_backups` (ADR-0002), which selects a backup mechanism per volume from
-/// this field. The field defaults to `full-snapshot` in configy (so the API
-/// always serves an explicit value), but a value `lucos_backups` doesn't
-/// recognise would silently fall back to full-snapshot — quietly defeating the
-/// opt-in — so guard it here the same way `recreate_effort` is guarded. If
-/// lucos_backups adds a new strategy, update this constant in lockstep.
-const RECOGNISED_BACKUP_STRATEGIES: [&str; 2] = [
-	"full-snapshot",
-	"incremental",
-];
-
+/// The allowed values of `recreate_effort` are enforced by `Data::from_dir` itself,
+/// but the field is optional in the data model, so check that every volume in this repo sets it.
+/// lucos_backups looks each volume's `recreate_effort` up unguarded, so a missing value crashes
+/// host-tracking for an entire host in production.  See lucas42/lucos_configy#221.
 #[test]
-fn backup_strategy_is_recognised() {
+fn recreate_effort_is_present() {
 	let data = load_test_data();
 
 	for volume in data.get_volumes() {
 		let id = volume.id.as_deref().unwrap_or("<unknown>");
 		assert!(
-			RECOGNISED_BACKUP_STRATEGIES.contains(&volume.backup_strategy.as_str()),
-			"Volume {:?} has unrecognised backup_strategy {:?}; it must be one of {:?}",
-			id, volume.backup_strategy, RECOGNISED_BACKUP_STRATEGIES
+			volume.recreate_effort.is_some(),
+			"Volume {:?} is missing recreate_effort; it must be one of {:?}",
+			id, RecreateEffort::ALL.iter().map(RecreateEffort::as_str).collect::<Vec<_>>()
 		);
 	}
 }
 
 #[test]
 fn config_files_are_sorted_alphabetically() {
-	let config_files = ["systems.yaml", "volumes.yaml", "hosts.yaml", "components.yaml", "scripts.yaml"];
+	let kinds = ["systems", "volumes", "hosts", "components", "scripts"];
 
-	for filename in &config_files {
-		let file_path = config_dir().join(filename);
-		let file = std::fs::File::open(&file_path)
-			.unwrap_or_else(|e| panic!("Failed to open {:?}: {}", file_path, e));
+	for kind in &kinds {
+		for filename in config_files(&config_dir(), kind) {
+			let file_path = config_dir().join(&filename);
+			let file = std::fs::File::open(&file_path)
+				.unwrap_or_else(|e| panic!("Failed to open {:?}: {}", file_path, e));
 
-		let value: Value = serde_yaml_ng::from_reader(file)
-			.unwrap_or_else(|e| panic!("Failed to parse {:?}: {}", file_path, e));
+			let value: Value = serde_yaml_ng::from_reader(file)
+				.unwrap_or_else(|e| panic!("Failed to parse {:?}: {}", file_path, e));
 
-		let mapping = value.as_mapping()
-			.unwrap_or_else(|| panic!("{} is not a YAML mapping", filename));
+			let mapping = value.as_mapping()
+				.unwrap_or_else(|| panic!("{} is not a YAML mapping", filename));
 
-		let keys: Vec<&str> = mapping.keys()
-			.map(|k| k.as_str().unwrap_or_else(|| panic!("Non-string key in {}", filename)))
-			.collect();
+			let keys: Vec<&str> = mapping.keys()
+				.map(|k| k.as_str().unwrap_or_else(|| panic!("Non-string key in {}", filename)))
+				.collect();
 
-		let mut sorted_keys = keys.clone();
-		sorted_keys.sort();
+			let mut sorted_keys = keys.clone();
+			sorted_keys.sort();
 
-		assert_eq!(
-			keys, sorted_keys,
-			"{} keys are not in alphabetical order. Got: {:?}",
-			filename, keys
-		);
+			assert_eq!(
+				keys, sorted_keys,
+				"{} keys are not in alphabetical order. Got: {:?}",
+				filename, keys
+			);
+		}
 	}
 }